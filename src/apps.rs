@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use sysinfo::{Pid, Process, ProcessStatus, ProcessesToUpdate, Signal, System};
+use tokio::time::{Duration, Instant, Interval, MissedTickBehavior};
+
+use crate::state::BlockedProcess;
+use crate::Config;
+
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// What the session does with a blocked application it finds running.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AppBlockPolicy {
+    /// Kill the process.
+    #[default]
+    Kill,
+    /// Leave it running and only record an `app` event.
+    Log,
+    /// Suspend the process (SIGSTOP) and let it continue when the session
+    /// ends, so no unsaved work is lost. Unix only.
+    Freeze,
+}
+
+/// What was done about one blocked process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppEnforcement {
+    pub name: String,
+    pub pid: u32,
+    pub outcome: AppOutcome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppOutcome {
+    Killed,
+    KillFailed,
+    Frozen,
+    FreezeFailed,
+    /// Found under the `log` policy and left alone.
+    Running,
+}
+
+impl std::fmt::Display for AppEnforcement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let outcome = match self.outcome {
+            AppOutcome::Killed => "killed",
+            AppOutcome::KillFailed => "could not be killed",
+            AppOutcome::Frozen => "frozen",
+            AppOutcome::FreezeFailed => "could not be frozen",
+            AppOutcome::Running => "running",
+        };
+        write!(f, "{} (PID {}) {}", self.name, self.pid, outcome)
+    }
+}
+
+impl AppEnforcement {
+    pub fn process(&self) -> BlockedProcess {
+        BlockedProcess { name: self.name.clone(), pid: self.pid }
+    }
+}
+
+/// Whether flowmode may act on `process`: on Unix only processes of the current
+/// user are touched, elsewhere the OS decides.
+fn owned_by_current_user(process: &Process) -> bool {
+    match process.user_id() {
+        #[cfg(unix)]
+        Some(uid) => **uid == nix::unistd::getuid().as_raw(),
+        #[cfg(not(unix))]
+        Some(_) => true,
+        None => true,
+    }
+}
+
+/// Keeps the applications in `app_block_list` closed for the whole session by
+/// looking for them again every `app_watch_interval`, so one relaunched
+/// mid-session is handled like the ones found at the start.
+pub struct AppWatcher {
+    apps: Vec<String>,
+    policy: AppBlockPolicy,
+    // Kept across checks, so other wake-ups of the session loop do not push
+    // the next one back
+    interval: Interval,
+    system: System,
+    // Processes already dealt with; a killed one can linger as a zombie until
+    // its parent reaps it
+    handled: HashSet<Pid>,
+    // Processes this watcher stopped, the only ones frozen again when continued
+    frozen: HashSet<Pid>,
+}
+
+impl AppWatcher {
+    pub fn from_config(config: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let interval = match &config.app_watch_interval {
+            Some(interval) => humantime::parse_duration(interval)
+                .map_err(|e| format!("Invalid app_watch_interval '{}': {}. Use format like '5s', '1m', etc.", interval, e))?,
+            None => DEFAULT_WATCH_INTERVAL,
+        };
+        let policy = config.app_block_policy.unwrap_or_default();
+        if policy == AppBlockPolicy::Freeze && !cfg!(unix) {
+            return Err("The freeze app_block_policy is only available on Unix-like systems".into());
+        }
+        let mut interval = tokio::time::interval_at(Instant::now() + interval, interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Ok(AppWatcher {
+            apps: config.app_block_list.clone().unwrap_or_default(),
+            policy,
+            interval,
+            system: System::new(),
+            handled: HashSet::new(),
+            frozen: HashSet::new(),
+        })
+    }
+
+    /// Whether there is anything to watch for.
+    pub fn is_empty(&self) -> bool {
+        self.apps.is_empty()
+    }
+
+    pub fn apps(&self) -> &[String] {
+        &self.apps
+    }
+
+    /// Waits until the next check is due, every `app_watch_interval`.
+    pub async fn tick(&mut self) {
+        self.interval.tick().await;
+    }
+
+    /// Looks for blocked applications started since the last check and
+    /// handles them according to `app_block_policy`.
+    pub fn enforce(&mut self) -> Vec<AppEnforcement> {
+        self.system.refresh_processes(ProcessesToUpdate::All);
+        let own_pid = sysinfo::get_current_pid().ok();
+        self.handled.retain(|pid| self.system.process(*pid).is_some());
+        self.frozen.retain(|pid| self.handled.contains(pid));
+
+        let mut enforcements = Vec::new();
+        for (pid, process) in self.system.processes() {
+            // A frozen process that was continued by hand is frozen again; one
+            // that could not be frozen is not retried
+            let continued = self.frozen.contains(pid) && process.status() != ProcessStatus::Stop;
+            if Some(*pid) == own_pid || (self.handled.contains(pid) && !continued) || !owned_by_current_user(process) {
+                continue;
+            }
+            let name = process.name().to_string_lossy();
+            let Some(app) = self.apps.iter().find(|app| **app == name) else {
+                continue;
+            };
+            let outcome = match self.policy {
+                AppBlockPolicy::Kill if process.kill() => AppOutcome::Killed,
+                AppBlockPolicy::Kill => AppOutcome::KillFailed,
+                AppBlockPolicy::Log => AppOutcome::Running,
+                AppBlockPolicy::Freeze if process.kill_with(Signal::Stop) == Some(true) => AppOutcome::Frozen,
+                AppBlockPolicy::Freeze => AppOutcome::FreezeFailed,
+            };
+            self.handled.insert(*pid);
+            if outcome == AppOutcome::Frozen {
+                self.frozen.insert(*pid);
+            } else {
+                self.frozen.remove(pid);
+            }
+            enforcements.push(AppEnforcement { name: app.clone(), pid: pid.as_u32(), outcome });
+        }
+        enforcements
+    }
+}
+
+/// Lets a process frozen by a session continue (SIGCONT). Returns false if it
+/// is gone, or its PID now belongs to another program.
+pub fn thaw(frozen: &BlockedProcess) -> bool {
+    let pid = Pid::from_u32(frozen.pid);
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::Some(&[pid]));
+    match system.process(pid) {
+        Some(process) if process.name().to_string_lossy() == frozen.name => process.kill_with(Signal::Continue) == Some(true),
+        _ => false,
+    }
+}
@@ -0,0 +1,185 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+use crate::state::{format_remaining, SessionState};
+use crate::BlockPageConfig;
+
+const DEFAULT_PORT: u16 = 80;
+// A browser sends its whole request head at once; anything slower or larger is not one
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+/// The page served when no `template` is configured.
+pub const DEFAULT_BLOCK_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{{domain}} is blocked</title>
+<style>
+body { font-family: system-ui, sans-serif; background: #f4f1ea; color: #2d2a26; display: flex; min-height: 100vh; margin: 0; align-items: center; justify-content: center; }
+main { max-width: 32rem; padding: 2rem; text-align: center; }
+h1 { font-size: 1.5rem; }
+.remaining { font-size: 3rem; font-variant-numeric: tabular-nums; margin: 1rem 0; }
+</style>
+</head>
+<body>
+<main>
+<h1>🌊 {{domain}} is blocked while you focus</h1>
+<p>Working on: <strong>{{task}}</strong></p>
+<p class="remaining">{{remaining}}</p>
+<p>left in this {{phase}} phase</p>
+</main>
+</body>
+</html>
+"#;
+
+/// Serves a page explaining the block to browsers that were sent to a
+/// redirect address, instead of leaving them with a connection error. Only
+/// plain HTTP is answered: a page for an HTTPS site would fail certificate
+/// validation anyway. The listeners stop when this is dropped.
+pub struct BlockPageServer {
+    addresses: Vec<SocketAddr>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl BlockPageServer {
+    /// Listens on `port` of every redirect address. An unspecified address
+    /// (`0.0.0.0`, `::`) is served on loopback rather than on every interface.
+    /// Addresses that cannot be bound are skipped with a warning, so only a
+    /// failure on all of them is an error. A relative `template` path is taken
+    /// from the directory of the configuration file.
+    pub async fn start(settings: &BlockPageConfig, redirect_addresses: &[IpAddr]) -> Result<Self, Box<dyn std::error::Error>> {
+        let template = match &settings.template {
+            Some(path) => {
+                let file = crate::paths::config_base_dir().join(path);
+                fs::read_to_string(&file).map_err(|e| format!("Cannot read block page template {}: {}", file.display(), e))?
+            }
+            None => DEFAULT_BLOCK_PAGE.to_string(),
+        };
+        let port = settings.port.unwrap_or(DEFAULT_PORT);
+
+        let mut ips: Vec<IpAddr> = Vec::new();
+        for address in redirect_addresses {
+            let ip = match address {
+                IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+                ip => *ip,
+            };
+            if !ips.contains(&ip) {
+                ips.push(ip);
+            }
+        }
+
+        let mut server = BlockPageServer { addresses: Vec::new(), tasks: Vec::new() };
+        let mut failures = Vec::new();
+        for ip in ips {
+            match TcpListener::bind(SocketAddr::new(ip, port)).await {
+                Ok(listener) => {
+                    server.addresses.push(listener.local_addr()?);
+                    server.tasks.push(tokio::spawn(serve(listener, template.clone())));
+                }
+                Err(e) => failures.push(format!("{} ({})", SocketAddr::new(ip, port), e)),
+            }
+        }
+        if server.addresses.is_empty() {
+            return Err(format!("Cannot serve the block page on {}", failures.join(", ")).into());
+        }
+        for failure in failures {
+            eprintln!("Warning: Cannot serve the block page on {}", failure);
+        }
+        Ok(server)
+    }
+
+    /// Where the page is served, with the ports actually bound.
+    pub fn addresses(&self) -> &[SocketAddr] {
+        &self.addresses
+    }
+}
+
+impl Drop for BlockPageServer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+async fn serve(listener: TcpListener, template: String) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let template = template.clone();
+        tokio::spawn(async move {
+            let _ = tokio::time::timeout(REQUEST_TIMEOUT, answer(stream, &template)).await;
+        });
+    }
+}
+
+async fn answer(mut stream: TcpStream, template: &str) -> std::io::Result<()> {
+    let mut head = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") && head.len() < MAX_REQUEST_HEAD {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let domain = request_host(&head).unwrap_or_default();
+
+    // The state is read for every request, so the page follows pauses and extensions
+    let body = render_block_page(template, SessionState::load().as_ref(), &domain);
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    if !head.starts_with("HEAD ") {
+        stream.write_all(body.as_bytes()).await?;
+    }
+    stream.shutdown().await
+}
+
+// The Host header without its port
+fn request_host(head: &str) -> Option<String> {
+    let host = head.lines().skip(1).find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim().eq_ignore_ascii_case("host").then(|| value.trim())
+    })?;
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    Some(host.trim_end_matches('.').to_ascii_lowercase())
+}
+
+/// Fills the `{{domain}}`, `{{task}}`, `{{remaining}}` and `{{phase}}`
+/// placeholders of `template` for the session in `state`.
+pub fn render_block_page(template: &str, state: Option<&SessionState>, domain: &str) -> String {
+    let (task, remaining, phase) = match state {
+        Some(state) => {
+            let mut remaining = format_remaining(state.remaining());
+            if state.paused.is_some() {
+                remaining.push_str(" (paused)");
+            }
+            (state.task.clone(), remaining, state.phase.to_string().to_lowercase())
+        }
+        None => ("your focus session".to_string(), "--:--".to_string(), "focus".to_string()),
+    };
+    let domain = if domain.is_empty() { "This site" } else { domain };
+    template
+        .replace("{{domain}}", &escape_html(domain))
+        .replace("{{task}}", &escape_html(&task))
+        .replace("{{remaining}}", &escape_html(&remaining))
+        .replace("{{phase}}", &escape_html(&phase))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&#39;")
+}
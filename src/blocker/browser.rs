@@ -0,0 +1,247 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{write_file_atomically, BlockPlan, Blocker};
+use crate::Config;
+
+const FIREFOX_POLICY_FILE: &str = "policies.json";
+// The admin's own policies.json as it was before the session, so it can be put back
+const FIREFOX_BACKUP_FILE: &str = "flowmode-backup.json";
+// Chromium merges every JSON file in its managed policy directory, so ours lives beside the others
+const CHROMIUM_POLICY_FILE: &str = "flowmode.json";
+
+#[cfg(target_os = "linux")]
+const DEFAULT_FIREFOX_DIR: Option<&str> = Some("/etc/firefox/policies");
+#[cfg(not(target_os = "linux"))]
+const DEFAULT_FIREFOX_DIR: Option<&str> = None;
+#[cfg(target_os = "linux")]
+const DEFAULT_CHROMIUM_DIRS: &[&str] = &["/etc/opt/chrome/policies/managed", "/etc/chromium/policies/managed"];
+#[cfg(not(target_os = "linux"))]
+const DEFAULT_CHROMIUM_DIRS: &[&str] = &[];
+
+/// Blocks websites inside the browsers through their enterprise policies: a
+/// `WebsiteFilter` in Firefox's `policies.json` and a `URLBlocklist` in a
+/// Chromium managed policy file. Both also turn off DNS-over-HTTPS, which
+/// would otherwise let the browser skip the hosts file and the local resolver.
+/// A `policies.json` that already existed is restored on revert.
+pub struct BrowserPolicyBlocker {
+    firefox_dir: Option<PathBuf>,
+    chromium_dirs: Vec<PathBuf>,
+}
+
+impl BrowserPolicyBlocker {
+    pub fn new(firefox_dir: Option<PathBuf>, chromium_dirs: Vec<PathBuf>) -> Self {
+        BrowserPolicyBlocker { firefox_dir, chromium_dirs }
+    }
+
+    /// Uses the `[browser_policy]` section of the configuration, falling back to
+    /// the system-wide policy directories on Linux.
+    pub fn from_config(config: &Config) -> Self {
+        let settings = config.browser_policy.clone().unwrap_or_default();
+        let firefox_dir = settings.firefox_dir.or(DEFAULT_FIREFOX_DIR.map(str::to_string)).filter(|dir| !dir.is_empty());
+        let chromium_dirs = settings
+            .chromium_dirs
+            .unwrap_or_else(|| DEFAULT_CHROMIUM_DIRS.iter().map(|dir| dir.to_string()).collect());
+        BrowserPolicyBlocker::new(firefox_dir.map(PathBuf::from), chromium_dirs.into_iter().map(PathBuf::from).collect())
+    }
+
+    fn firefox_files(&self) -> Option<(PathBuf, PathBuf)> {
+        self.firefox_dir.as_ref().map(|dir| (dir.join(FIREFOX_POLICY_FILE), dir.join(FIREFOX_BACKUP_FILE)))
+    }
+
+    fn chromium_files(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.chromium_dirs.iter().map(|dir| dir.join(CHROMIUM_POLICY_FILE))
+    }
+
+    // policies.json as it should look during the session: the original one
+    // with our filter and DNS-over-HTTPS settings added
+    fn firefox_document(&self, policy_file: &Path, backup_file: &Path, plan: &BlockPlan) -> Result<Value, Box<dyn std::error::Error>> {
+        let original = original_firefox_policies(policy_file, backup_file)?;
+        let mut document = match original {
+            Some(content) => serde_json::from_str::<Value>(&content)
+                .map_err(|e| format!("Refusing to change {}: it is not valid JSON ({})", policy_file.display(), e))?,
+            None => json!({ "policies": {} }),
+        };
+        let policies = document
+            .as_object_mut()
+            .map(|document| document.entry("policies").or_insert_with(|| json!({})))
+            .and_then(Value::as_object_mut)
+            .ok_or_else(|| format!("Refusing to change {}: unexpected layout", policy_file.display()))?;
+        policies.insert("WebsiteFilter".to_string(), firefox_website_filter(plan));
+        policies.insert("DNSOverHTTPS".to_string(), json!({ "Enabled": false, "Locked": true }));
+        Ok(document)
+    }
+}
+
+impl Blocker for BrowserPolicyBlocker {
+    fn describe(&self) -> String {
+        let dirs: Vec<String> = self.firefox_dir.iter().chain(&self.chromium_dirs).map(|dir| dir.display().to_string()).collect();
+        format!("browser policies in {}", dirs.join(", "))
+    }
+
+    fn apply(&mut self, plan: &BlockPlan) -> Result<(), Box<dyn std::error::Error>> {
+        if self.firefox_dir.is_none() && self.chromium_dirs.is_empty() {
+            return Err("No browser policy directories are configured; set firefox_dir or chromium_dirs in the [browser_policy] section of config.toml".into());
+        }
+
+        if let Some((policy_file, backup_file)) = self.firefox_files() {
+            let document = self.firefox_document(&policy_file, &backup_file, plan)?;
+            create_policy_dir(&policy_file)?;
+            if !backup_file.exists() {
+                let original = fs::read_to_string(&policy_file).ok();
+                fs::write(&backup_file, serde_json::to_string_pretty(&json!({ "original": original }))?)?;
+            }
+            write_file_atomically(&policy_file, &serde_json::to_string_pretty(&document)?)?;
+        }
+        for policy_file in self.chromium_files() {
+            create_policy_dir(&policy_file)?;
+            write_file_atomically(&policy_file, &serde_json::to_string_pretty(&chromium_policy(plan))?)?;
+        }
+        println!("🧭 Browser policies written; restart open browsers for them to take effect");
+        Ok(())
+    }
+
+    fn verify(&self, plan: &BlockPlan) -> Result<(), Box<dyn std::error::Error>> {
+        let read = |path: &Path| -> Result<Value, Box<dyn std::error::Error>> {
+            let content = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+            Ok(serde_json::from_str(&content).map_err(|e| format!("{} is not valid JSON: {}", path.display(), e))?)
+        };
+
+        if let Some((policy_file, _)) = self.firefox_files() {
+            if read(&policy_file)?["policies"]["WebsiteFilter"] != firefox_website_filter(plan) {
+                return Err(format!("{} no longer holds the flowmode website filter", policy_file.display()).into());
+            }
+        }
+        for policy_file in self.chromium_files() {
+            if read(&policy_file)? != chromium_policy(plan) {
+                return Err(format!("{} was changed", policy_file.display()).into());
+            }
+        }
+        Ok(())
+    }
+
+    fn revert(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some((policy_file, backup_file)) = self.firefox_files() {
+            if let Ok(backup) = fs::read_to_string(&backup_file) {
+                let backup: Value = serde_json::from_str(&backup)
+                    .map_err(|e| format!("Cannot restore {}: {} is damaged ({})", policy_file.display(), backup_file.display(), e))?;
+                match backup["original"].as_str() {
+                    Some(original) => write_file_atomically(&policy_file, original)?,
+                    None => remove_if_present(&policy_file)?,
+                }
+                fs::remove_file(&backup_file)?;
+                println!("Restored Firefox policies in {}", policy_file.display());
+            }
+        }
+        for policy_file in self.chromium_files() {
+            if policy_file.exists() {
+                remove_if_present(&policy_file)?;
+                println!("Removed Chromium policy {}", policy_file.display());
+            }
+        }
+        Ok(())
+    }
+
+    fn watched_paths(&self) -> Vec<PathBuf> {
+        self.firefox_files().map(|(policy_file, _)| policy_file).into_iter().chain(self.chromium_files()).collect()
+    }
+}
+
+// The content policies.json had before the session, or None if there was none.
+// Once the session has written its own version the backup is the source.
+fn original_firefox_policies(policy_file: &Path, backup_file: &Path) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    match fs::read_to_string(backup_file) {
+        Ok(backup) => {
+            let backup: Value = serde_json::from_str(&backup).map_err(|e| format!("{} is damaged: {}", backup_file.display(), e))?;
+            Ok(backup["original"].as_str().map(str::to_string))
+        }
+        Err(_) => Ok(fs::read_to_string(policy_file).ok()),
+    }
+}
+
+fn create_policy_dir(policy_file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = policy_file.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create browser policy directory {}: {}", dir.display(), e))?;
+    }
+    Ok(())
+}
+
+fn remove_if_present(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("Failed to remove {}: {}", path.display(), e).into()),
+        _ => Ok(()),
+    }
+}
+
+// Every blocked domain, however it is written in `block_list`
+fn blocked_domains(plan: &BlockPlan) -> Vec<String> {
+    let mut domains: Vec<String> = Vec::new();
+    for entry in &plan.block_list {
+        let Some(host) = entry.split_whitespace().last() else {
+            continue;
+        };
+        let domain = host.strip_prefix("*.").unwrap_or(host).trim_end_matches('.').to_ascii_lowercase();
+        if !domains.contains(&domain) {
+            domains.push(domain);
+        }
+    }
+    domains
+}
+
+// Allowlist entries as (host, whether everything below it is allowed too)
+fn allowed_hosts(allowlist: &[String], subdomains: &[String]) -> Vec<(String, bool)> {
+    let mut hosts = Vec::new();
+    for entry in allowlist {
+        let entry = entry.trim().trim_end_matches('.').to_ascii_lowercase();
+        match entry.strip_prefix("*.") {
+            Some(domain) => hosts.push((domain.to_string(), true)),
+            None => {
+                hosts.push((entry.clone(), false));
+                hosts.extend(subdomains.iter().map(|subdomain| (format!("{}.{}", subdomain, entry), false)));
+            }
+        }
+    }
+    hosts
+}
+
+/// Firefox `WebsiteFilter` policy for `plan`, using match patterns.
+fn firefox_website_filter(plan: &BlockPlan) -> Value {
+    match &plan.allowlist {
+        Some(allowlist) => {
+            let exceptions: Vec<String> = allowed_hosts(allowlist, &plan.subdomains)
+                .into_iter()
+                .flat_map(|(host, subtree)| {
+                    let mut patterns = vec![format!("*://{}/*", host)];
+                    if subtree {
+                        patterns.push(format!("*://*.{}/*", host));
+                    }
+                    patterns
+                })
+                .collect();
+            json!({ "Block": ["<all_urls>"], "Exceptions": exceptions })
+        }
+        None => {
+            let block: Vec<String> = blocked_domains(plan)
+                .into_iter()
+                .flat_map(|domain| [format!("*://{}/*", domain), format!("*://*.{}/*", domain)])
+                .collect();
+            json!({ "Block": block })
+        }
+    }
+}
+
+/// Chromium managed policy for `plan`. A plain host in a URL filter also
+/// matches its subdomains; a leading dot restricts it to the host itself.
+fn chromium_policy(plan: &BlockPlan) -> Value {
+    match &plan.allowlist {
+        Some(allowlist) => {
+            let allowed: Vec<String> = allowed_hosts(allowlist, &plan.subdomains)
+                .into_iter()
+                .map(|(host, subtree)| if subtree { host } else { format!(".{}", host) })
+                .collect();
+            json!({ "URLBlocklist": ["*"], "URLAllowlist": allowed, "DnsOverHttpsMode": "off" })
+        }
+        None => json!({ "URLBlocklist": blocked_domains(plan), "DnsOverHttpsMode": "off" }),
+    }
+}
@@ -0,0 +1,113 @@
+use std::net::SocketAddr;
+
+use super::{BlockPlan, Blocker};
+use crate::dns::{parse_dns_address, system_upstream, DnsFilter, DnsServer};
+use crate::Config;
+
+const DEFAULT_DNS_LISTEN: &str = "127.0.0.1:53";
+
+/// Runs a local DNS resolver for the session. In whitelist mode it answers
+/// every name outside the allowlist with the redirect addresses; otherwise it
+/// does that for names in `block_list` and everything below them. All other
+/// queries are forwarded upstream.
+pub struct DnsBlocker {
+    listen: SocketAddr,
+    upstream: SocketAddr,
+    server: Option<DnsServer>,
+    announced: bool,
+}
+
+impl DnsBlocker {
+    pub fn new(listen: SocketAddr, upstream: SocketAddr) -> Self {
+        DnsBlocker { listen, upstream, server: None, announced: false }
+    }
+
+    /// Uses the `[dns]` section of the configuration.
+    pub fn from_config(config: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let dns_config = config.dns.clone().unwrap_or_default();
+        let listen = parse_dns_address(dns_config.listen.as_deref().unwrap_or(DEFAULT_DNS_LISTEN))?;
+        let upstream = match &dns_config.upstream {
+            Some(upstream) => parse_dns_address(upstream)?,
+            None => system_upstream(listen),
+        };
+        Ok(DnsBlocker::new(listen, upstream))
+    }
+
+    /// Where the resolver is answering while applied.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.as_ref().map(DnsServer::local_addr)
+    }
+}
+
+impl Blocker for DnsBlocker {
+    fn describe(&self) -> String {
+        format!("DNS resolver on {}", self.local_addr().unwrap_or(self.listen))
+    }
+
+    fn apply(&mut self, plan: &BlockPlan) -> Result<(), Box<dyn std::error::Error>> {
+        if self.server.as_ref().is_some_and(DnsServer::is_running) {
+            return Ok(());
+        }
+
+        let filter = match &plan.allowlist {
+            Some(allowlist) => {
+                for domain in allowlist {
+                    println!("Whitelisted domain: {}", domain);
+                }
+                DnsFilter::allowlist(allowlist, &plan.subdomains)
+            }
+            None => DnsFilter::blocklist(&plan.block_list),
+        };
+        let server = DnsServer::start(self.listen, self.upstream, filter, plan.redirect_addresses.clone()).map_err(|e| {
+            if self.listen.port() < 1024 {
+                format!("{}. Listening on port {} needs administrator rights; run flowmode with sudo or set `listen` in the [dns] section of config.toml to a port above 1023.", e, self.listen.port())
+            } else {
+                e.to_string()
+            }
+        })?;
+
+        if !self.announced {
+            println!("🌐 DNS resolver listening on {} (forwarding to {})", server.local_addr(), self.upstream);
+            print_resolver_guidance(server.local_addr());
+            self.announced = true;
+        }
+        self.server = Some(server);
+        Ok(())
+    }
+
+    fn verify(&self, _plan: &BlockPlan) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.server {
+            Some(server) if server.is_running() => Ok(()),
+            Some(_) => Err("the DNS resolver stopped unexpectedly".into()),
+            None => Err("the DNS resolver is not running".into()),
+        }
+    }
+
+    fn update(&mut self, plan: &BlockPlan) -> Result<(), Box<dyn std::error::Error>> {
+        match self.server.as_ref().filter(|server| server.is_running()) {
+            // Swapped in place, since a new server could not bind the address
+            // until the old one has fully shut down
+            Some(server) if plan.allowlist.is_none() => {
+                server.set_filter(DnsFilter::blocklist(&plan.block_list));
+                Ok(())
+            }
+            _ => self.apply(plan),
+        }
+    }
+
+    fn revert(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        // Dropping the server stops it
+        self.server = None;
+        Ok(())
+    }
+}
+
+fn print_resolver_guidance(resolver: SocketAddr) {
+    println!("\n📋 FlowMode is blocking websites through a local DNS resolver.");
+    println!("   Make {} your system's DNS server for the session to take effect", resolver);
+    if resolver.port() != 53 {
+        println!("   (most systems only query port 53, so forward to it from a local DNS forwarder)");
+    }
+    println!("   • Browsers with DNS-over-HTTPS enabled bypass the system resolver; turn it off");
+    println!("   • Cached lookups may keep working for a minute after the session starts\n");
+}
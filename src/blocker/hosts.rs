@@ -0,0 +1,243 @@
+use std::collections::HashSet;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use super::{write_file_atomically, BlockPlan, Blocker};
+use crate::paths;
+
+const HOSTS_BLOCK_BEGIN: &str = "# BEGIN flowmode";
+const HOSTS_BLOCK_END: &str = "# END flowmode";
+
+/// Blocks websites with entries between `# BEGIN flowmode` and `# END flowmode`
+/// markers in a hosts file. Reverting removes only that block, so edits made to
+/// the rest of the file during a session are kept.
+pub struct HostsBlocker {
+    path: PathBuf,
+}
+
+impl HostsBlocker {
+    pub fn new(path: PathBuf) -> Self {
+        HostsBlocker { path }
+    }
+}
+
+impl Blocker for HostsBlocker {
+    fn describe(&self) -> String {
+        format!("hosts file {}", self.path.display())
+    }
+
+    fn apply(&mut self, plan: &BlockPlan) -> Result<(), Box<dyn std::error::Error>> {
+        if plan.allowlist.is_some() {
+            return Err("A hosts file cannot block everything except an allowlist; use the dns backend for whitelist mode".into());
+        }
+
+        let original_content = if self.path.exists() {
+            fs::read_to_string(&self.path)?
+        } else {
+            String::new()
+        };
+
+        // Replace any block left by an earlier run so blocking stays idempotent
+        let unmanaged_content = strip_managed_block(&original_content);
+        let mut new_content = unmanaged_content.clone();
+        if !new_content.is_empty() && !new_content.ends_with('\n') {
+            new_content.push('\n');
+        }
+
+        let existing = hosts_mappings(&unmanaged_content);
+
+        new_content.push_str(HOSTS_BLOCK_BEGIN);
+        new_content.push('\n');
+        // Explain the purpose of a user-level hosts file inside the block, so it goes away with it
+        if !is_system_hosts(&self.path) && unmanaged_content.trim().is_empty() {
+            new_content.push_str("# FlowMode user-level hosts file\n");
+            new_content.push_str("# This file blocks websites without requiring admin privileges\n");
+            new_content.push_str("# Note: This only works if you configure your system to use this as an additional hosts source\n");
+        }
+        for (address, host) in hosts_entries(plan) {
+            // Already redirected the same way outside our block
+            if !existing.contains(&(address.clone(), host.clone())) {
+                new_content.push_str(&format!("{} {}\n", address, host));
+            }
+        }
+        new_content.push_str(HOSTS_BLOCK_END);
+        new_content.push('\n');
+
+        write_hosts_file(&self.path, &new_content)
+    }
+
+    fn verify(&self, plan: &BlockPlan) -> Result<(), Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(&self.path)
+            .map_err(|e| format!("Cannot read {}: {}", self.path.display(), e))?;
+        let present = hosts_mappings(&content);
+        let missing: Vec<String> = hosts_entries(plan)
+            .into_iter()
+            .filter(|entry| !present.contains(entry))
+            .map(|(address, host)| format!("{} {}", address, host))
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("{} is missing {} entries: {}", self.path.display(), missing.len(), missing.join(", ")).into())
+        }
+    }
+
+    fn revert(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let legacy_backup = legacy_hosts_backup_file(&self.path);
+
+        let content = fs::read_to_string(&self.path).unwrap_or_default();
+        let restored = strip_managed_block(&content);
+
+        if restored != content {
+            if !is_system_hosts(&self.path) && restored.trim().is_empty() {
+                // Nothing but our own entries lived in the user-level hosts file
+                fs::remove_file(&self.path)?;
+                println!("Removed user hosts file");
+            } else {
+                write_hosts_file(&self.path, &restored)?;
+                println!("Removed flowmode entries from hosts file");
+            }
+        } else if let Ok(backup_content) = fs::read_to_string(&legacy_backup) {
+            // A session started by an older version, which kept a full copy instead of markers
+            write_hosts_file(&self.path, &backup_content)?;
+            println!("Restored hosts file from legacy backup {}", legacy_backup.display());
+        } else {
+            println!("No flowmode entries found, hosts file not modified");
+        }
+
+        if legacy_backup.exists() {
+            if let Err(e) = fs::remove_file(&legacy_backup) {
+                eprintln!("Warning: Failed to remove backup file: {}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn watched_paths(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
+}
+
+pub(crate) fn is_system_hosts(path: &Path) -> bool {
+    path.to_string_lossy().contains("System32") || path.to_string_lossy().contains("/etc/")
+}
+
+// Backups written by versions that restored the whole hosts file on unblock
+pub(crate) fn legacy_hosts_backup_file(hosts_path: &Path) -> PathBuf {
+    if is_system_hosts(hosts_path) {
+        paths::state_dir().join("hosts.backup")
+    } else {
+        paths::state_dir().join("user_hosts.backup")
+    }
+}
+
+/// Replaces the hosts file at `path` with `content`; see [`write_file_atomically`].
+pub fn write_hosts_file(path: &Path, content: &str) -> Result<(), Box<dyn std::error::Error>> {
+    write_file_atomically(path, content)
+}
+
+/// Returns `content` with every complete flowmode marker block removed and all
+/// other lines left exactly as they were.
+pub fn strip_managed_block(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut pending_block = String::new();
+    let mut in_block = false;
+
+    for line in content.split_inclusive('\n') {
+        if in_block {
+            pending_block.push_str(line);
+            if line.trim() == HOSTS_BLOCK_END {
+                pending_block.clear();
+                in_block = false;
+            }
+        } else if line.trim() == HOSTS_BLOCK_BEGIN {
+            pending_block.push_str(line);
+            in_block = true;
+        } else {
+            result.push_str(line);
+        }
+    }
+
+    // A begin marker without its end marker is not ours to remove
+    result.push_str(&pending_block);
+    result
+}
+
+pub(crate) fn has_managed_block(content: &str) -> bool {
+    strip_managed_block(content) != content
+}
+
+/// Turns the plan's `block_list` into `(address, hostname)` pairs for the hosts file.
+///
+/// A plain domain such as `reddit.com` is redirected to every one of the
+/// plan's redirect addresses (127.0.0.1 and ::1 by default), together with
+/// each of its subdomains (`www.reddit.com` by default). Entries in the
+/// older `"127.0.0.1 reddit.com"` hosts line format keep their address and get
+/// the redirect addresses of the other IP family added, so the host is
+/// blocked over both IPv4 and IPv6. A hosts file has no wildcards, so a
+/// leading `*.` as in `*.reddit.com` is dropped and the entry is handled like
+/// `reddit.com`. Duplicates are dropped and hostnames are lowercased.
+pub fn hosts_entries(plan: &BlockPlan) -> Vec<(String, String)> {
+    let mut entries: Vec<(String, String)> = Vec::new();
+    let mut seen = HashSet::new();
+    let mut push = |address: &IpAddr, host: &str| {
+        let entry = (address.to_string(), host.to_string());
+        if seen.insert(entry.clone()) {
+            entries.push(entry);
+        }
+    };
+
+    for item in &plan.block_list {
+        let mut fields = item.split_whitespace();
+        let (Some(first), second) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        match second {
+            Some(host) => {
+                // Legacy "IP hostname" line
+                let Ok(address) = first.parse::<IpAddr>() else {
+                    eprintln!("Warning: Ignoring block_list entry '{}': '{}' is not an IP address", item, first);
+                    continue;
+                };
+                let host = host.strip_prefix("*.").unwrap_or(host).to_ascii_lowercase();
+                push(&address, &host);
+                for other in plan.redirect_addresses.iter().filter(|other| other.is_ipv4() != address.is_ipv4()) {
+                    push(other, &host);
+                }
+            }
+            None => {
+                let domain = first.strip_prefix("*.").unwrap_or(first).trim_end_matches('.').to_ascii_lowercase();
+                let mut hosts = vec![domain.clone()];
+                for subdomain in &plan.subdomains {
+                    // "www.example.com" in the list should not also produce "www.www.example.com"
+                    if !domain.starts_with(&format!("{}.", subdomain)) {
+                        hosts.push(format!("{}.{}", subdomain, domain));
+                    }
+                }
+                for host in &hosts {
+                    for address in &plan.redirect_addresses {
+                        push(address, host);
+                    }
+                }
+            }
+        }
+    }
+    entries
+}
+
+// Every (address, hostname) mapping in a hosts file, ignoring comments.
+fn hosts_mappings(content: &str) -> HashSet<(String, String)> {
+    let mut mappings = HashSet::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        if let Some(address) = fields.next() {
+            for host in fields {
+                mappings.insert((address.to_string(), host.to_ascii_lowercase()));
+            }
+        }
+    }
+    mappings
+}
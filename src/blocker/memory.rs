@@ -0,0 +1,61 @@
+use std::sync::{Arc, Mutex};
+
+use super::{BlockPlan, Blocker};
+
+/// Keeps the block in memory instead of touching the system, for tests.
+/// Clones share their state, so a test can keep one handle and give the
+/// other to the code under test.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBlocker {
+    state: Arc<Mutex<MemoryState>>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    applied: Option<BlockPlan>,
+    calls: Vec<&'static str>,
+}
+
+impl MemoryBlocker {
+    pub fn new() -> Self {
+        MemoryBlocker::default()
+    }
+
+    /// The plan that is currently applied, if any.
+    pub fn applied(&self) -> Option<BlockPlan> {
+        self.state.lock().unwrap().applied.clone()
+    }
+
+    /// Every `apply` and `revert` call so far, in order.
+    pub fn calls(&self) -> Vec<&'static str> {
+        self.state.lock().unwrap().calls.clone()
+    }
+}
+
+impl Blocker for MemoryBlocker {
+    fn describe(&self) -> String {
+        "in-memory blocker".to_string()
+    }
+
+    fn apply(&mut self, plan: &BlockPlan) -> Result<(), Box<dyn std::error::Error>> {
+        let mut state = self.state.lock().unwrap();
+        state.applied = Some(plan.clone());
+        state.calls.push("apply");
+        Ok(())
+    }
+
+    fn verify(&self, plan: &BlockPlan) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.state.lock().unwrap().applied {
+            Some(applied) if applied == plan => Ok(()),
+            Some(_) => Err("a different plan is applied".into()),
+            None => Err("nothing is applied".into()),
+        }
+    }
+
+    fn revert(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut state = self.state.lock().unwrap();
+        state.applied = None;
+        state.calls.push("revert");
+        Ok(())
+    }
+}
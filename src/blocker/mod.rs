@@ -0,0 +1,302 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use crate::{BlockingBackend, Config};
+
+mod browser;
+mod dns;
+mod hosts;
+#[cfg(any(test, feature = "test-util"))]
+mod memory;
+mod nftables;
+
+pub use browser::BrowserPolicyBlocker;
+pub use dns::DnsBlocker;
+pub(crate) use hosts::{has_managed_block, is_system_hosts, legacy_hosts_backup_file};
+pub use hosts::{hosts_entries, strip_managed_block, write_hosts_file, HostsBlocker};
+#[cfg(any(test, feature = "test-util"))]
+pub use memory::MemoryBlocker;
+pub use nftables::{nftables_ruleset, NftablesBlocker};
+
+const DEFAULT_REDIRECT_ADDRESSES: &[&str] = &["127.0.0.1", "::1"];
+const DEFAULT_SUBDOMAINS: &[&str] = &["www"];
+const MAX_CHECKED_HOSTS: usize = 32;
+
+/// A way of keeping websites out of reach for the duration of a session.
+/// Which ones a session uses is chosen with `blocking_backends` in `config.toml`.
+pub trait Blocker: Send {
+    /// Short description for status output, such as "hosts file /etc/hosts".
+    fn describe(&self) -> String;
+
+    /// Starts blocking what `plan` asks for. Called again after `revert` when a
+    /// pause that lifted the blocks ends.
+    fn apply(&mut self, plan: &BlockPlan) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Switches to a changed `plan` while applied, as when a schedule window
+    /// opens or closes.
+    fn update(&mut self, plan: &BlockPlan) -> Result<(), Box<dyn std::error::Error>> {
+        self.apply(plan)
+    }
+
+    /// Checks that what `apply` put in place is still there.
+    fn verify(&self, plan: &BlockPlan) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Undoes `apply`. Does nothing if the block is not in place.
+    fn revert(&mut self) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Files holding the block, watched during a session so edits to them are
+    /// noticed right away.
+    fn watched_paths(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+/// Replaces the file at `path` with `content` without ever exposing a
+/// partially written file: the new content is written and fsynced to a temporary
+/// file in the same directory, given the original file's permissions (and owner
+/// on Unix), then renamed over the original. If `path` is a symlink, the file
+/// it points to is replaced and the link is kept.
+pub fn write_file_atomically(path: &Path, content: &str) -> Result<(), Box<dyn std::error::Error>> {
+    // Renaming over the link itself would turn it into a regular file
+    let resolved = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            fs::canonicalize(path).map_err(|e| format!("Failed to resolve the symlink {}: {}", path.display(), e))?
+        }
+        _ => path.to_path_buf(),
+    };
+    let path = resolved.as_path();
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name().ok_or_else(|| format!("Invalid file path: {}", path.display()))?;
+    let temp_path = dir.join(format!(".{}.flowmode.tmp", file_name.to_string_lossy()));
+
+    let write_temp = || -> std::io::Result<()> {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(content.as_bytes())?;
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&temp_path, metadata.permissions())?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                let temp_metadata = file.metadata()?;
+                if (temp_metadata.uid(), temp_metadata.gid()) != (metadata.uid(), metadata.gid()) {
+                    std::os::unix::fs::chown(&temp_path, Some(metadata.uid()), Some(metadata.gid()))?;
+                }
+            }
+        }
+        file.sync_all()
+    };
+    if let Err(e) = write_temp() {
+        let _ = fs::remove_file(&temp_path);
+        return Err(format!("Failed to prepare the new version of {} in its directory: {}", path.display(), e).into());
+    }
+
+    if let Err(e) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(match e.kind() {
+            // Renaming onto a mount point, e.g. a file bind-mounted into a container
+            std::io::ErrorKind::CrossesDevices | std::io::ErrorKind::ResourceBusy => format!(
+                "Cannot atomically replace {}: it is on a different filesystem than its directory ({}). Refusing to rewrite it in place.",
+                path.display(), e
+            ),
+            _ => format!("Failed to replace {}: {}", path.display(), e),
+        }
+        .into());
+    }
+
+    // Make the rename itself durable
+    #[cfg(unix)]
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
+
+/// Looks up the addresses of a hostname. Passed in so lookups can be faked in tests.
+pub type HostResolver = fn(&str) -> std::io::Result<Vec<IpAddr>>;
+
+/// Resolves `host` the way other programs on the system would.
+pub fn system_resolver(host: &str) -> std::io::Result<Vec<IpAddr>> {
+    dns_lookup::lookup_host(host).map(|addresses| addresses.collect())
+}
+
+/// What a session blocks, resolved from the configuration once at start.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockPlan {
+    /// Entries from `block_list`: plain domains or `"IP hostname"` lines.
+    pub block_list: Vec<String>,
+    /// Set in `--whitelist` mode, where everything but these names is blocked
+    /// and `block_list` is ignored.
+    pub allowlist: Option<Vec<String>>,
+    /// Subdomains covered along with every plain domain, such as `www`.
+    pub subdomains: Vec<String>,
+    /// Where blocked names are sent instead.
+    pub redirect_addresses: Vec<IpAddr>,
+}
+
+impl BlockPlan {
+    pub fn from_config(config: &Config, whitelist: bool) -> Self {
+        let subdomains = match &config.subdomains {
+            Some(subdomains) => subdomains.iter().map(|s| s.trim().trim_matches('.').to_string()).filter(|s| !s.is_empty()).collect(),
+            None => DEFAULT_SUBDOMAINS.iter().map(|s| s.to_string()).collect(),
+        };
+        let redirect_addresses = match &config.redirect_addresses {
+            Some(configured) => configured
+                .iter()
+                .filter_map(|address| match address.trim().parse::<IpAddr>() {
+                    Ok(address) => Some(address),
+                    Err(_) => {
+                        eprintln!("Warning: Ignoring invalid redirect address '{}'", address);
+                        None
+                    }
+                })
+                .collect(),
+            None => DEFAULT_REDIRECT_ADDRESSES.iter().filter_map(|address| address.parse().ok()).collect(),
+        };
+
+        BlockPlan {
+            block_list: config.block_list.clone().unwrap_or_default(),
+            allowlist: whitelist.then(|| config.whitelist.clone().unwrap_or_default()),
+            subdomains,
+            redirect_addresses,
+        }
+    }
+
+    /// Adds domains from imported block lists, skipping the ones `block_list`
+    /// already names. Returns how many were added.
+    pub fn add_domains(&mut self, domains: impl IntoIterator<Item = String>) -> usize {
+        let mut known: HashSet<String> = self.block_list.iter().map(|entry| entry.trim().trim_end_matches('.').to_ascii_lowercase()).collect();
+        let before = self.block_list.len();
+        for domain in domains {
+            if known.insert(domain.clone()) {
+                self.block_list.push(domain);
+            }
+        }
+        self.block_list.len() - before
+    }
+
+    /// Drops the entries for `domains` from `block_list`, whatever form they are
+    /// written in. Returns how many were removed.
+    pub fn remove_domains(&mut self, domains: &[String]) -> usize {
+        let allowed: HashSet<String> = domains.iter().map(|domain| domain.trim().trim_end_matches('.').to_ascii_lowercase()).collect();
+        let before = self.block_list.len();
+        self.block_list.retain(|entry| {
+            let host = entry.split_whitespace().last().unwrap_or_default();
+            let host = host.strip_prefix("*.").unwrap_or(host).trim_end_matches('.').to_ascii_lowercase();
+            !allowed.contains(&host)
+        });
+        before - self.block_list.len()
+    }
+}
+
+pub(crate) fn uses_backend(config: &Config, backend: BlockingBackend) -> bool {
+    match &config.blocking_backends {
+        Some(backends) => backends.contains(&backend),
+        None => backend == BlockingBackend::Hosts,
+    }
+}
+
+/// Creates the backends selected in `config`. Whitelist mode is enforced by
+/// the DNS resolver, together with browser policies if those are selected;
+/// the other backends cannot express it.
+pub fn build_blockers(config: &Config, whitelist: bool) -> Result<Vec<Box<dyn Blocker>>, Box<dyn std::error::Error>> {
+    if whitelist {
+        let mut blockers: Vec<Box<dyn Blocker>> = vec![Box::new(DnsBlocker::from_config(config)?)];
+        if uses_backend(config, BlockingBackend::Browser) {
+            blockers.push(Box::new(BrowserPolicyBlocker::from_config(config)));
+        }
+        return Ok(blockers);
+    }
+
+    let mut blockers: Vec<Box<dyn Blocker>> = Vec::new();
+    for backend in config.blocking_backends.clone().unwrap_or_else(|| vec![BlockingBackend::Hosts]) {
+        match backend {
+            BlockingBackend::Hosts => blockers.push(Box::new(HostsBlocker::new(crate::get_hosts_path()))),
+            BlockingBackend::Dns => blockers.push(Box::new(DnsBlocker::from_config(config)?)),
+            BlockingBackend::Nftables => {
+                if !cfg!(target_os = "linux") {
+                    return Err("The nftables blocking backend is only available on Linux".into());
+                }
+                // Goes first, so the domains are resolved before the other
+                // backends start redirecting them
+                blockers.insert(0, Box::new(NftablesBlocker::from_config(config)));
+            }
+            BlockingBackend::Browser => blockers.push(Box::new(BrowserPolicyBlocker::from_config(config))),
+        }
+    }
+    Ok(blockers)
+}
+
+/// A blocked hostname that still resolves to addresses outside the block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnblockedHost {
+    pub host: String,
+    pub addresses: Vec<IpAddr>,
+}
+
+/// Looks up every hostname the plan blocks with `resolve` and returns the ones
+/// that still reach a real address, meaning the system is not using what the
+/// blockers put in place. Loopback, unspecified and redirect addresses count as
+/// blocked, and so does a name that does not resolve at all. The lookups run in
+/// parallel so one slow name does not hold up the rest. Only the first 32
+/// hostnames are checked, so imported block lists do not set off thousands of
+/// lookups.
+pub fn check_resolution(plan: &BlockPlan, resolve: HostResolver) -> Vec<UnblockedHost> {
+    let mut hosts: Vec<(String, Vec<IpAddr>)> = Vec::new();
+    for (address, host) in hosts_entries(plan) {
+        let address = address.parse::<IpAddr>().ok();
+        match hosts.iter().position(|(known, _)| *known == host) {
+            Some(index) => hosts[index].1.extend(address),
+            None if hosts.len() < MAX_CHECKED_HOSTS => hosts.push((host, address.into_iter().collect())),
+            None => break,
+        }
+    }
+
+    std::thread::scope(|scope| {
+        let lookups: Vec<_> = hosts
+            .iter()
+            .map(|(host, expected)| scope.spawn(move || {
+                let resolved = resolve(host).unwrap_or_default();
+                let unblocked: Vec<IpAddr> = resolved
+                    .into_iter()
+                    .filter(|address| {
+                        !address.is_loopback() && !address.is_unspecified() && !expected.contains(address) && !plan.redirect_addresses.contains(address)
+                    })
+                    .collect();
+                (!unblocked.is_empty()).then(|| UnblockedHost { host: host.clone(), addresses: unblocked })
+            }))
+            .collect();
+        lookups.into_iter().filter_map(|lookup| lookup.join().ok().flatten()).collect()
+    })
+}
+
+/// Applies every blocker in order. If one fails, the ones already applied are
+/// reverted again so a failed start leaves nothing behind.
+pub fn apply_blockers(blockers: &mut [Box<dyn Blocker>], plan: &BlockPlan) -> Result<(), Box<dyn std::error::Error>> {
+    for index in 0..blockers.len() {
+        if let Err(e) = blockers[index].apply(plan) {
+            let error = format!("Failed to block websites with {}: {}", blockers[index].describe(), e);
+            revert_blockers(&mut blockers[..index]);
+            return Err(error.into());
+        }
+    }
+    Ok(())
+}
+
+/// Reverts every blocker, in reverse order, even if some of them fail. Returns
+/// the failures as warnings.
+pub fn revert_blockers(blockers: &mut [Box<dyn Blocker>]) -> Vec<String> {
+    let mut failures = Vec::new();
+    for blocker in blockers.iter_mut().rev() {
+        if let Err(e) = blocker.revert() {
+            failures.push(format!("Failed to unblock websites with {}: {}", blocker.describe(), e));
+        }
+    }
+    failures
+}
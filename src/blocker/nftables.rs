@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::IpAddr;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::runtime::RuntimeFlavor;
+
+use super::{hosts_entries, system_resolver, BlockPlan, Blocker, HostResolver};
+use crate::Config;
+
+const TABLE: &str = "inet flowmode";
+// Lookups run this many at a time
+const RESOLVE_CONCURRENCY: usize = 16;
+// A lookup taking longer is given up on, like the check after blocking does
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(2);
+// No lookups are started after this long, so imported block lists with
+// thousands of domains cannot hold up the session start for minutes
+const RESOLVE_BUDGET: Duration = Duration::from_secs(20);
+// Failed lookups named in the warning before the rest are only counted
+const MAX_REPORTED_FAILURES: usize = 5;
+
+/// Blocks websites at the firewall on Linux: the blocked domains are resolved
+/// when the session starts and a dedicated `inet flowmode` nftables table
+/// rejects outgoing traffic to those addresses. Unlike the hosts file this
+/// also stops programs that cached the addresses or resolve names themselves.
+/// In dry-run mode the ruleset is printed instead of installed, which needs no
+/// root.
+///
+/// Each domain is resolved once while the table is installed: when the plan
+/// changes or the table is put back after tampering, only newly blocked
+/// domains are looked up, since by then the other backends answer the known
+/// ones with a redirect address. Lookups run 16 at a time and are given up on
+/// after 2 seconds each, and none are started after 20 seconds; the skipped
+/// hostnames are counted in a warning. On a multi-threaded Tokio runtime the
+/// lookups leave the runtime's other tasks running.
+pub struct NftablesBlocker {
+    dry_run: bool,
+    resolve: HostResolver,
+    addresses: Vec<IpAddr>,
+    // What each blocked hostname resolved to; a failed lookup is not kept, so
+    // it is tried again on the next apply
+    resolved: HashMap<String, Vec<IpAddr>>,
+    applied: bool,
+}
+
+impl NftablesBlocker {
+    pub fn new(dry_run: bool) -> Self {
+        NftablesBlocker::with_resolver(dry_run, system_resolver)
+    }
+
+    /// Uses `resolve` instead of the system resolver to look up blocked domains.
+    pub fn with_resolver(dry_run: bool, resolve: HostResolver) -> Self {
+        NftablesBlocker { dry_run, resolve, addresses: Vec::new(), resolved: HashMap::new(), applied: false }
+    }
+
+    /// Uses the `[nftables]` section of the configuration.
+    pub fn from_config(config: &Config) -> Self {
+        let dry_run = config.nftables.as_ref().and_then(|nftables| nftables.dry_run).unwrap_or(false);
+        NftablesBlocker::new(dry_run)
+    }
+
+    /// Addresses rejected since the last `apply`.
+    pub fn addresses(&self) -> &[IpAddr] {
+        &self.addresses
+    }
+
+    // Every address the blocked hostnames resolve to, looking up only those
+    // not resolved before. Loopback and unspecified addresses are skipped:
+    // they are what other backends redirect to, and rejecting them would break
+    // local services.
+    fn resolve_plan(&mut self, plan: &BlockPlan) -> Vec<IpAddr> {
+        let mut hosts: Vec<String> = Vec::new();
+        for (_, host) in hosts_entries(plan) {
+            if !hosts.contains(&host) {
+                hosts.push(host);
+            }
+        }
+        self.resolved.retain(|host, _| hosts.contains(host));
+
+        let pending: Vec<String> = hosts.iter().filter(|host| !self.resolved.contains_key(*host)).cloned().collect();
+        let lookups = resolve_hosts(&pending, self.resolve);
+        let (mut failed, mut skipped) = (Vec::new(), 0);
+        for (host, lookup) in pending.into_iter().zip(lookups) {
+            match lookup {
+                Some(Ok(resolved)) => {
+                    let resolved = resolved.into_iter().filter(|address| !address.is_loopback() && !address.is_unspecified()).collect();
+                    self.resolved.insert(host, resolved);
+                }
+                Some(Err(e)) => failed.push(format!("{} ({})", host, e)),
+                None => skipped += 1,
+            }
+        }
+        if !failed.is_empty() {
+            let more = failed.len().saturating_sub(MAX_REPORTED_FAILURES);
+            failed.truncate(MAX_REPORTED_FAILURES);
+            let more = if more > 0 { format!(" and {} more", more) } else { String::new() };
+            eprintln!("Warning: Could not resolve {}{} for the firewall", failed.join(", "), more);
+        }
+        if skipped > 0 {
+            eprintln!(
+                "Warning: Skipped {} of {} blocked hostnames for the firewall: their lookups did not answer in time",
+                skipped,
+                hosts.len()
+            );
+        }
+
+        let mut addresses = Vec::new();
+        for host in &hosts {
+            for address in self.resolved.get(host).into_iter().flatten() {
+                if !addresses.contains(address) {
+                    addresses.push(*address);
+                }
+            }
+        }
+        addresses
+    }
+}
+
+// Looks up `hosts` with `resolve`, a few at a time, and returns the result for
+// each in the same order. A hostname whose lookup timed out or was never
+// started because the time budget ran out has no result.
+fn resolve_hosts(hosts: &[String], resolve: HostResolver) -> Vec<Option<std::io::Result<Vec<IpAddr>>>> {
+    // The lookups can take up to the whole budget; on a session's runtime the
+    // worker thread is handed over first, so the control server, block page
+    // and DNS backend keep answering meanwhile
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(|| lookup_hosts(hosts, resolve))
+        }
+        _ => lookup_hosts(hosts, resolve),
+    }
+}
+
+fn lookup_hosts(hosts: &[String], resolve: HostResolver) -> Vec<Option<std::io::Result<Vec<IpAddr>>>> {
+    let started = Instant::now();
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..hosts.len()).map(|_| None).collect::<Vec<_>>());
+    std::thread::scope(|scope| {
+        for _ in 0..RESOLVE_CONCURRENCY.min(hosts.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= hosts.len() || started.elapsed() >= RESOLVE_BUDGET {
+                    break;
+                }
+                let host = hosts[index].clone();
+                let (sender, receiver) = mpsc::channel();
+                // A detached thread, so a lookup still hanging after the timeout
+                // does not hold up the rest
+                std::thread::spawn(move || {
+                    let _ = sender.send(resolve(&host));
+                });
+                if let Ok(result) = receiver.recv_timeout(RESOLVE_TIMEOUT) {
+                    results.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+    results.into_inner().unwrap()
+}
+
+impl Blocker for NftablesBlocker {
+    fn describe(&self) -> String {
+        if self.dry_run {
+            format!("nftables table {} (dry run)", TABLE)
+        } else {
+            format!("nftables table {}", TABLE)
+        }
+    }
+
+    fn apply(&mut self, plan: &BlockPlan) -> Result<(), Box<dyn std::error::Error>> {
+        if plan.allowlist.is_some() {
+            return Err("The firewall cannot block everything except an allowlist; use the dns backend for whitelist mode".into());
+        }
+
+        let addresses = self.resolve_plan(plan);
+        if addresses.is_empty() {
+            eprintln!("Warning: None of the blocked domains resolved to an address the firewall could reject");
+        }
+        // Deleting first replaces a table left by an earlier run; declaring it
+        // beforehand keeps the delete from failing when there is none
+        let script = format!("table {table}\ndelete table {table}\n{}", nftables_ruleset(&addresses), table = TABLE);
+
+        if self.dry_run {
+            println!("🧱 nftables dry run, not installing:\n{}", nftables_ruleset(&addresses));
+        } else {
+            run_nft(&["-f", "-"], Some(&script))?;
+            println!("🧱 Rejecting traffic to {} addresses with nftables table {}", addresses.len(), TABLE);
+        }
+        self.addresses = addresses;
+        self.applied = true;
+        Ok(())
+    }
+
+    fn verify(&self, _plan: &BlockPlan) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.applied {
+            return Err(format!("nftables table {} has not been installed", TABLE).into());
+        }
+        if self.dry_run {
+            return Ok(());
+        }
+        run_nft(&["list", "table", "inet", "flowmode"], None)
+            .map(|_| ())
+            .map_err(|e| format!("nftables table {} is gone: {}", TABLE, e).into())
+    }
+
+    fn revert(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        // Always try, so recovery also removes a table left by a crashed session
+        if self.dry_run {
+            if self.applied {
+                println!("🧱 nftables dry run, not running: nft delete table {}", TABLE);
+            }
+        } else {
+            match run_nft(&["delete", "table", "inet", "flowmode"], None) {
+                Ok(_) => println!("Removed nftables table {}", TABLE),
+                Err(NftError::NotInstalled) => {}
+                Err(NftError::Failed(message)) if message.contains("No such file or directory") => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        self.applied = false;
+        self.addresses.clear();
+        self.resolved.clear();
+        Ok(())
+    }
+}
+
+/// The `inet flowmode` table for `addresses`: one set per IP family and an
+/// output chain that rejects everything sent to them.
+pub fn nftables_ruleset(addresses: &[IpAddr]) -> String {
+    let set = |name: &str, kind: &str, members: Vec<String>| {
+        if members.is_empty() {
+            format!("    set {} {{\n        type {}\n    }}\n", name, kind)
+        } else {
+            format!("    set {} {{\n        type {}\n        elements = {{ {} }}\n    }}\n", name, kind, members.join(", "))
+        }
+    };
+    let ipv4 = addresses.iter().filter(|address| address.is_ipv4()).map(IpAddr::to_string).collect();
+    let ipv6 = addresses.iter().filter(|address| address.is_ipv6()).map(IpAddr::to_string).collect();
+
+    let mut ruleset = format!("table {} {{\n", TABLE);
+    ruleset.push_str(&set("blocked_ipv4", "ipv4_addr", ipv4));
+    ruleset.push_str(&set("blocked_ipv6", "ipv6_addr", ipv6));
+    ruleset.push_str("    chain output {\n");
+    ruleset.push_str("        type filter hook output priority 0; policy accept;\n");
+    ruleset.push_str("        ip daddr @blocked_ipv4 reject\n");
+    ruleset.push_str("        ip6 daddr @blocked_ipv6 reject\n");
+    ruleset.push_str("    }\n");
+    ruleset.push_str("}\n");
+    ruleset
+}
+
+#[derive(Debug)]
+enum NftError {
+    NotInstalled,
+    Failed(String),
+}
+
+impl std::fmt::Display for NftError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NftError::NotInstalled => write!(f, "the nft command was not found; install nftables to use this backend"),
+            NftError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for NftError {}
+
+fn run_nft(args: &[&str], input: Option<&str>) -> Result<String, NftError> {
+    let mut child = Command::new("nft")
+        .args(args)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => NftError::NotInstalled,
+            _ => NftError::Failed(format!("Failed to run nft: {}", e)),
+        })?;
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|e| NftError::Failed(format!("Failed to pass the ruleset to nft: {}", e)))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| NftError::Failed(format!("Failed to run nft: {}", e)))?;
+
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if stderr.contains("Operation not permitted") {
+        Err(NftError::Failed(format!("nft {} failed: {}. Changing the firewall needs root; run flowmode with sudo or set dry_run in the [nftables] section of config.toml.", args.join(" "), stderr)))
+    } else {
+        Err(NftError::Failed(format!("nft {} failed: {}", args.join(" "), stderr)))
+    }
+}
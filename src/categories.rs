@@ -0,0 +1,64 @@
+use crate::Config;
+
+/// What a session blocks on top of `block_list`, resolved from the
+/// `--block` and `--allow` options.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Selection {
+    /// Names of the categories to block, in the order given.
+    pub categories: Vec<String>,
+    /// Entries to block: the domains of the chosen categories, then the
+    /// domains named directly.
+    pub block: Vec<String>,
+    /// Domains that stay reachable for the session, including every domain of
+    /// a category passed to `--allow`.
+    pub allow: Vec<String>,
+}
+
+/// Resolves `block` and `allow`, each a list of category names from the
+/// `[categories]` table and plain domains. A name that is neither a category
+/// nor looks like a domain is an error, so a typo does not silently block
+/// nothing.
+pub fn select_categories(config: &Config, block: &[String], allow: &[String]) -> Result<Selection, Box<dyn std::error::Error>> {
+    let mut selection = Selection::default();
+    for name in block {
+        match resolve(config, name)? {
+            Resolved::Category(category, domains) => {
+                if !selection.categories.contains(&category) {
+                    selection.categories.push(category);
+                    selection.block.extend(domains);
+                }
+            }
+            Resolved::Domain(domain) => selection.block.push(domain),
+        }
+    }
+    for name in allow {
+        match resolve(config, name)? {
+            Resolved::Category(_, domains) => selection.allow.extend(domains),
+            Resolved::Domain(domain) => selection.allow.push(domain),
+        }
+    }
+    Ok(selection)
+}
+
+enum Resolved {
+    Category(String, Vec<String>),
+    Domain(String),
+}
+
+fn resolve(config: &Config, name: &str) -> Result<Resolved, Box<dyn std::error::Error>> {
+    let name = name.trim().to_ascii_lowercase();
+    let categories = config.categories.clone().unwrap_or_default();
+    if let Some((category, domains)) = categories.iter().find(|(category, _)| category.to_ascii_lowercase() == name) {
+        return Ok(Resolved::Category(category.clone(), domains.clone()));
+    }
+    if name.contains('.') && !name.contains(char::is_whitespace) {
+        return Ok(Resolved::Domain(name.trim_end_matches('.').to_string()));
+    }
+
+    if categories.is_empty() {
+        Err(format!("Unknown category '{}': no categories are defined in the [categories] section of config.toml", name).into())
+    } else {
+        let known: Vec<&str> = categories.keys().map(String::as_str).collect();
+        Err(format!("Unknown category '{}'. Available categories: {}", name, known.join(", ")).into())
+    }
+}
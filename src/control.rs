@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+// How long a client waits for the running session to answer. Stopping includes
+// restoring the hosts file and unmuting audio, so this is deliberately generous.
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A command sent from a `flowmode` invocation to the running session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlRequest {
+    Stop,
+    Pause { lift_blocks: bool },
+    Resume,
+    /// Moves the end of the current phase, or of the whole session, by `seconds`
+    /// (negative values shorten it).
+    Adjust { seconds: i64, whole_session: bool },
+}
+
+/// The running session's answer to a [`ControlRequest`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ControlResponse {
+    pub ok: bool,
+    pub message: String,
+}
+
+impl ControlResponse {
+    pub fn ok(message: impl Into<String>) -> Self {
+        ControlResponse { ok: true, message: message.into() }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        ControlResponse { ok: false, message: message.into() }
+    }
+}
+
+/// A request received by the session, together with the channel used to answer it.
+pub struct ControlMessage {
+    pub request: ControlRequest,
+    pub reply: oneshot::Sender<ControlResponse>,
+}
+
+/// Location of the control endpoint: a Unix domain socket on Unix, and a file
+/// holding the loopback TCP port elsewhere.
+pub fn control_endpoint_path() -> PathBuf {
+    if cfg!(unix) {
+        crate::paths::state_dir().join("flowmode.sock")
+    } else {
+        crate::paths::state_dir().join("flowmode.port")
+    }
+}
+
+/// Listens on the control endpoint for the lifetime of a session and forwards
+/// every request to the session loop.
+pub struct ControlServer {
+    receiver: mpsc::Receiver<ControlMessage>,
+    accept_task: JoinHandle<()>,
+    // Every connection task holds a clone of the sender; the channel closes once
+    // they have all finished writing their responses.
+    connections_done: mpsc::Receiver<()>,
+    path: PathBuf,
+}
+
+impl ControlServer {
+    pub async fn bind() -> Result<Self, Box<dyn std::error::Error>> {
+        let path = control_endpoint_path();
+        let (sender, receiver) = mpsc::channel(8);
+        let (connection_guard, connections_done) = mpsc::channel(1);
+        let accept_task = spawn_listener(&path, sender, connection_guard).await?;
+        Ok(ControlServer { receiver, accept_task, connections_done, path })
+    }
+
+    pub async fn recv(&mut self) -> Option<ControlMessage> {
+        self.receiver.recv().await
+    }
+
+    /// Stops accepting requests, waits briefly for pending responses to be
+    /// delivered and removes the endpoint file.
+    pub async fn close(mut self) {
+        self.accept_task.abort();
+        let _ = std::fs::remove_file(&self.path);
+        let _ = tokio::time::timeout(Duration::from_secs(5), self.connections_done.recv()).await;
+    }
+}
+
+#[cfg(unix)]
+async fn spawn_listener(path: &std::path::Path, sender: mpsc::Sender<ControlMessage>, connection_guard: mpsc::Sender<()>) -> Result<JoinHandle<()>, Box<dyn std::error::Error>> {
+    // A socket file left behind by a crashed session would make bind fail
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)
+        .map_err(|e| format!("Failed to open control socket at {}: {}", path.display(), e))?;
+
+    Ok(tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve_connection(stream, sender.clone(), connection_guard.clone()));
+        }
+    }))
+}
+
+#[cfg(not(unix))]
+async fn spawn_listener(path: &std::path::Path, sender: mpsc::Sender<ControlMessage>, connection_guard: mpsc::Sender<()>) -> Result<JoinHandle<()>, Box<dyn std::error::Error>> {
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0)).await
+        .map_err(|e| format!("Failed to open control port: {}", e))?;
+    std::fs::write(path, listener.local_addr()?.port().to_string())?;
+
+    Ok(tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve_connection(stream, sender.clone(), connection_guard.clone()));
+        }
+    }))
+}
+
+// Each connection carries exactly one JSON request line and one JSON response line.
+async fn serve_connection<S>(stream: S, sender: mpsc::Sender<ControlMessage>, _guard: mpsc::Sender<()>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+    if stream.read_line(&mut line).await.is_err() {
+        return;
+    }
+
+    let response = match serde_json::from_str::<ControlRequest>(line.trim()) {
+        Ok(request) => {
+            let (reply, answer) = oneshot::channel();
+            if sender.send(ControlMessage { request, reply }).await.is_err() {
+                ControlResponse::error("Session is shutting down")
+            } else {
+                answer.await.unwrap_or_else(|_| ControlResponse::error("Session ended without answering"))
+            }
+        }
+        Err(e) => ControlResponse::error(format!("Invalid control request: {}", e)),
+    };
+
+    if let Ok(mut payload) = serde_json::to_string(&response) {
+        payload.push('\n');
+        let _ = stream.get_mut().write_all(payload.as_bytes()).await;
+    }
+}
+
+/// Sends a request to the running session. Returns `Ok(None)` when no session is
+/// listening on the control endpoint.
+pub async fn send_request(request: &ControlRequest) -> Result<Option<ControlResponse>, Box<dyn std::error::Error>> {
+    let path = control_endpoint_path();
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    #[cfg(unix)]
+    let stream = match tokio::net::UnixStream::connect(&path).await {
+        Ok(stream) => stream,
+        Err(e) if is_not_listening(&e) => return Ok(None),
+        Err(e) => return Err(format!("Failed to connect to control socket {}: {}", path.display(), e).into()),
+    };
+
+    #[cfg(not(unix))]
+    let stream = {
+        let port: u16 = match std::fs::read_to_string(&path).ok().and_then(|p| p.trim().parse().ok()) {
+            Some(port) => port,
+            None => return Ok(None),
+        };
+        match tokio::net::TcpStream::connect(("127.0.0.1", port)).await {
+            Ok(stream) => stream,
+            Err(e) if is_not_listening(&e) => return Ok(None),
+            Err(e) => return Err(format!("Failed to connect to control port {}: {}", port, e).into()),
+        }
+    };
+
+    let exchange = async {
+        let mut stream = BufReader::new(stream);
+        let mut payload = serde_json::to_string(request)?;
+        payload.push('\n');
+        stream.get_mut().write_all(payload.as_bytes()).await?;
+
+        let mut line = String::new();
+        stream.read_line(&mut line).await?;
+        let response: ControlResponse = serde_json::from_str(line.trim())
+            .map_err(|e| format!("Invalid response from running session: {}", e))?;
+        Ok::<_, Box<dyn std::error::Error>>(response)
+    };
+
+    match tokio::time::timeout(CONTROL_TIMEOUT, exchange).await {
+        Ok(response) => response.map(Some),
+        Err(_) => Err("Timed out waiting for the running session to respond".into()),
+    }
+}
+
+fn is_not_listening(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused
+    )
+}
@@ -0,0 +1,329 @@
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::task::JoinHandle;
+
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(5);
+const FALLBACK_UPSTREAM: &str = "1.1.1.1:53";
+// Short, so names become reachable again soon after the session ends
+const BLOCKED_TTL: u32 = 60;
+const MAX_PACKET_SIZE: usize = 4096;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+const RCODE_NXDOMAIN: u8 = 3;
+
+/// One entry of a domain list: `example.com` matches exactly that name,
+/// `*.example.com` matches `example.com` and every name below it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DomainPattern {
+    Exact(String),
+    Subtree(String),
+}
+
+impl DomainPattern {
+    fn parse(pattern: &str) -> Option<Self> {
+        let pattern = pattern.trim().trim_end_matches('.').to_ascii_lowercase();
+        if let Some(domain) = pattern.strip_prefix("*.") {
+            return (!domain.is_empty() && !domain.contains('*')).then(|| DomainPattern::Subtree(domain.to_string()));
+        }
+        (!pattern.is_empty() && !pattern.contains('*')).then_some(DomainPattern::Exact(pattern))
+    }
+}
+
+/// Decides which names the local resolver answers itself instead of forwarding.
+/// Lookups take time proportional to the length of the name, not the number of
+/// patterns, so imported block lists with many thousands of domains stay fast.
+#[derive(Debug, Clone)]
+pub struct DnsFilter {
+    exact: HashSet<String>,
+    subtrees: HashSet<String>,
+    // Whether the patterns list the only names that are let through
+    allowlist: bool,
+}
+
+impl DnsFilter {
+    /// Blocks every name except those matching `whitelist`. A plain domain
+    /// also allows its `subdomains` variants (such as `www.`); `*.example.com`
+    /// allows `example.com` and everything below it. Invalid patterns are
+    /// reported and skipped.
+    pub fn allowlist(whitelist: &[String], subdomains: &[String]) -> Self {
+        let mut allowed = Vec::new();
+        for entry in whitelist {
+            match DomainPattern::parse(entry) {
+                Some(DomainPattern::Exact(domain)) => {
+                    for subdomain in subdomains {
+                        allowed.push(DomainPattern::Exact(format!("{}.{}", subdomain, domain)));
+                    }
+                    allowed.push(DomainPattern::Exact(domain));
+                }
+                Some(pattern) => allowed.push(pattern),
+                None => eprintln!("Warning: Ignoring invalid whitelist entry '{}'", entry),
+            }
+        }
+        DnsFilter::new(allowed, true)
+    }
+
+    /// Blocks every name in `block_list` together with all names below it, so
+    /// `reddit.com` also covers `old.reddit.com`. `*.reddit.com` means the same,
+    /// and entries in the hosts line format (`"127.0.0.1 reddit.com"`) block only
+    /// that exact host.
+    pub fn blocklist(block_list: &[String]) -> Self {
+        let mut blocked = Vec::new();
+        for entry in block_list {
+            let mut fields = entry.split_whitespace();
+            let (Some(first), second) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let pattern = match second {
+                Some(host) => DomainPattern::parse(host),
+                None => DomainPattern::parse(first.strip_prefix("*.").unwrap_or(first))
+                    .map(|pattern| match pattern {
+                        DomainPattern::Exact(domain) | DomainPattern::Subtree(domain) => DomainPattern::Subtree(domain),
+                    }),
+            };
+            match pattern {
+                Some(pattern) => blocked.push(pattern),
+                None => eprintln!("Warning: Ignoring invalid block_list entry '{}'", entry),
+            }
+        }
+        DnsFilter::new(blocked, false)
+    }
+
+    fn new(patterns: Vec<DomainPattern>, allowlist: bool) -> Self {
+        let mut filter = DnsFilter { exact: HashSet::new(), subtrees: HashSet::new(), allowlist };
+        for pattern in patterns {
+            match pattern {
+                DomainPattern::Exact(domain) => filter.exact.insert(domain),
+                DomainPattern::Subtree(domain) => filter.subtrees.insert(domain),
+            };
+        }
+        filter
+    }
+
+    pub fn is_blocked(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        self.matches(&name) != self.allowlist
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        if self.exact.contains(name) {
+            return true;
+        }
+        // The name itself and every parent domain, e.g. a.b.com, b.com and com
+        let mut suffix = name;
+        loop {
+            if self.subtrees.contains(suffix) {
+                return true;
+            }
+            match suffix.split_once('.') {
+                Some((_, parent)) => suffix = parent,
+                None => return false,
+            }
+        }
+    }
+}
+
+/// A small DNS server on UDP that answers blocked names with the redirect
+/// addresses and forwards everything else to an upstream resolver. It runs
+/// until the handle is dropped.
+pub struct DnsServer {
+    local_addr: SocketAddr,
+    task: JoinHandle<()>,
+    resolver: Arc<Resolver>,
+}
+
+struct Resolver {
+    filter: RwLock<DnsFilter>,
+    redirect: Vec<IpAddr>,
+    upstream: SocketAddr,
+}
+
+impl DnsServer {
+    /// Binds `listen` and starts serving on the current Tokio runtime.
+    pub fn start(listen: SocketAddr, upstream: SocketAddr, filter: DnsFilter, redirect: Vec<IpAddr>) -> Result<Self, Box<dyn std::error::Error>> {
+        let socket = std::net::UdpSocket::bind(listen)
+            .map_err(|e| format!("Failed to listen for DNS queries on {}: {}", listen, e))?;
+        socket.set_nonblocking(true)?;
+        let socket = UdpSocket::from_std(socket)?;
+        let local_addr = socket.local_addr()?;
+        let socket = Arc::new(socket);
+        let resolver = Arc::new(Resolver { filter: RwLock::new(filter), redirect, upstream });
+
+        let server_resolver = resolver.clone();
+        let task = tokio::spawn(async move {
+            let resolver = server_resolver;
+            let mut buffer = vec![0u8; MAX_PACKET_SIZE];
+            loop {
+                // Errors here are per packet (e.g. ICMP port unreachable reported on Windows)
+                let Ok((len, client)) = socket.recv_from(&mut buffer).await else {
+                    continue;
+                };
+                let query = buffer[..len].to_vec();
+                let socket = socket.clone();
+                let resolver = resolver.clone();
+                tokio::spawn(async move {
+                    if let Some(response) = resolver.answer(&query).await {
+                        let _ = socket.send_to(&response, client).await;
+                    }
+                });
+            }
+        });
+
+        Ok(DnsServer { local_addr, task, resolver })
+    }
+
+    /// Replaces which names are blocked without interrupting the server.
+    pub fn set_filter(&self, filter: DnsFilter) {
+        *self.resolver.filter.write().unwrap() = filter;
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+}
+
+impl Drop for DnsServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Resolver {
+    async fn answer(&self, query: &[u8]) -> Option<Vec<u8>> {
+        let question = parse_question(query)?;
+        let blocked = self.filter.read().unwrap().is_blocked(&question.name);
+        if blocked {
+            return Some(blocked_response(query, &question, &self.redirect));
+        }
+        match forward(query, self.upstream).await {
+            Ok(response) => Some(response),
+            Err(e) => {
+                eprintln!("Warning: DNS lookup of {} via {} failed: {}", question.name, self.upstream, e);
+                None
+            }
+        }
+    }
+}
+
+struct Question {
+    name: String,
+    qtype: u16,
+    // Offset of the first byte after the question section
+    end: usize,
+}
+
+// Only plain queries with a single question are handled; anything else is dropped.
+fn parse_question(packet: &[u8]) -> Option<Question> {
+    if packet.len() < 12 || packet[2] & 0x80 != 0 || u16::from_be_bytes([packet[4], packet[5]]) != 1 {
+        return None;
+    }
+
+    let mut labels = Vec::new();
+    let mut pos = 12;
+    loop {
+        let len = *packet.get(pos)? as usize;
+        pos += 1;
+        if len == 0 {
+            break;
+        }
+        // Compression pointers do not appear in the question of a query
+        if len & 0xC0 != 0 {
+            return None;
+        }
+        labels.push(String::from_utf8_lossy(packet.get(pos..pos + len)?).to_ascii_lowercase());
+        pos += len;
+    }
+    let qtype = u16::from_be_bytes([*packet.get(pos)?, *packet.get(pos + 1)?]);
+    packet.get(pos + 2..pos + 4)?;
+
+    Some(Question { name: labels.join("."), qtype, end: pos + 4 })
+}
+
+// Answers A and AAAA queries with the redirect addresses of that family and
+// every other type with an empty answer. Without any redirect addresses the
+// name is reported as nonexistent.
+fn blocked_response(query: &[u8], question: &Question, redirect: &[IpAddr]) -> Vec<u8> {
+    let answers: Vec<&IpAddr> = redirect
+        .iter()
+        .filter(|address| match question.qtype {
+            TYPE_A => address.is_ipv4(),
+            TYPE_AAAA => address.is_ipv6(),
+            _ => false,
+        })
+        .collect();
+
+    let mut response = query[..question.end].to_vec();
+    // QR and AA set, opcode and RD copied from the query
+    response[2] = 0x84 | (query[2] & 0x79);
+    // RA set
+    response[3] = if redirect.is_empty() { 0x80 | RCODE_NXDOMAIN } else { 0x80 };
+    response[6..8].copy_from_slice(&(answers.len() as u16).to_be_bytes());
+    // No authority or additional records; an EDNS option in the query is dropped
+    response[8..12].fill(0);
+
+    for address in answers {
+        // Pointer to the name in the question
+        response.extend_from_slice(&[0xC0, 0x0C]);
+        let (rtype, data) = match address {
+            IpAddr::V4(v4) => (TYPE_A, v4.octets().to_vec()),
+            IpAddr::V6(v6) => (TYPE_AAAA, v6.octets().to_vec()),
+        };
+        response.extend_from_slice(&rtype.to_be_bytes());
+        response.extend_from_slice(&CLASS_IN.to_be_bytes());
+        response.extend_from_slice(&BLOCKED_TTL.to_be_bytes());
+        response.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        response.extend_from_slice(&data);
+    }
+    response
+}
+
+async fn forward(query: &[u8], upstream: SocketAddr) -> std::io::Result<Vec<u8>> {
+    let local: SocketAddr = if upstream.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(upstream).await?;
+    socket.send(query).await?;
+
+    let mut buffer = vec![0u8; MAX_PACKET_SIZE];
+    let len = tokio::time::timeout(UPSTREAM_TIMEOUT, socket.recv(&mut buffer))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "no answer"))??;
+    buffer.truncate(len);
+    Ok(buffer)
+}
+
+/// Parses `1.1.1.1`, `1.1.1.1:53`, `::1` or `[::1]:5353`; the port defaults to 53.
+pub fn parse_dns_address(address: &str) -> Result<SocketAddr, Box<dyn std::error::Error>> {
+    let address = address.trim();
+    if let Ok(socket_addr) = address.parse::<SocketAddr>() {
+        return Ok(socket_addr);
+    }
+    address
+        .parse::<IpAddr>()
+        .map(|ip| SocketAddr::new(ip, 53))
+        .map_err(|_| format!("Invalid DNS server address '{}'. Use a form like '127.0.0.1:53'.", address).into())
+}
+
+/// The first nameserver from /etc/resolv.conf that is not the local resolver
+/// itself, or a public resolver if there is none.
+pub fn system_upstream(listen: SocketAddr) -> SocketAddr {
+    let configured = std::fs::read_to_string("/etc/resolv.conf")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|server| server.trim().parse::<IpAddr>().ok())
+        .map(|ip| SocketAddr::new(ip, 53))
+        .find(|server| *server != listen && !(listen.ip().is_unspecified() && server.port() == listen.port() && server.ip().is_loopback()));
+    configured.unwrap_or_else(|| FALLBACK_UPSTREAM.parse().expect("valid fallback address"))
+}
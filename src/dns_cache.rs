@@ -0,0 +1,88 @@
+use std::process::{Command, Stdio};
+
+/// Runs external commands. The system implementation spawns real processes;
+/// tests substitute one that records the calls.
+pub trait CommandRunner {
+    /// Runs `program` with `args` and waits for it, failing if it cannot be
+    /// started or exits unsuccessfully.
+    fn run(&self, program: &str, args: &[&str]) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+/// Runs commands as child processes with their output discarded.
+pub struct SystemCommandRunner;
+
+impl CommandRunner for SystemCommandRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => "not installed".to_string(),
+                _ => e.to_string(),
+            })?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if stderr.is_empty() {
+            Err(format!("exited with {}", output.status).into())
+        } else {
+            Err(stderr.into())
+        }
+    }
+}
+
+/// The commands that clear the resolver caches commonly found on this platform.
+pub fn default_flush_commands() -> Vec<String> {
+    let commands: &[&str] = if cfg!(target_os = "windows") {
+        &["ipconfig /flushdns"]
+    } else if cfg!(target_os = "macos") {
+        // Both are needed: the first clears the directory service cache, the second mDNSResponder's
+        &["dscacheutil -flushcache", "killall -HUP mDNSResponder"]
+    } else {
+        &["resolvectl flush-caches", "nscd --invalidate=hosts"]
+    };
+    commands.iter().map(|command| command.to_string()).collect()
+}
+
+/// The result of running one flush command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlushResult {
+    pub command: String,
+    pub error: Option<String>,
+}
+
+/// Runs every command in `commands`, since a system may run more than one
+/// cache (e.g. systemd-resolved and nscd), and returns how each one went.
+pub fn flush_dns_caches(commands: &[String], runner: &dyn CommandRunner) -> Vec<FlushResult> {
+    commands
+        .iter()
+        .filter_map(|command| {
+            let mut words = command.split_whitespace();
+            let program = words.next()?;
+            let args: Vec<&str> = words.collect();
+            Some(FlushResult {
+                command: command.trim().to_string(),
+                error: runner.run(program, &args).err().map(|e| e.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Prints which flush commands succeeded, or why none did. Commands for caches
+/// that are simply not present are only mentioned when nothing worked.
+pub fn report_flush(results: &[FlushResult]) {
+    let flushed: Vec<&str> = results.iter().filter(|result| result.error.is_none()).map(|result| result.command.as_str()).collect();
+    if !flushed.is_empty() {
+        println!("🧹 Flushed DNS cache with: {}", flushed.join(", "));
+    } else if !results.is_empty() {
+        let failures: Vec<String> = results
+            .iter()
+            .map(|result| format!("`{}` ({})", result.command, result.error.as_deref().unwrap_or_default()))
+            .collect();
+        eprintln!("Warning: Could not flush the DNS cache, browsers may keep using cached answers for a few minutes. Tried {}", failures.join(", "));
+    }
+}
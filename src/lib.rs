@@ -1,29 +1,172 @@
 use clap::{Parser, Subcommand};
-use reqwest;
-use serde_json;
 use std::fs;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use tokio::process::Command;
 use std::process::Stdio;
-use sysinfo::System;
+use sysinfo::{Pid, ProcessesToUpdate, System};
 use chrono::prelude::*;
 use std::io::Write;
-use tokio::sync::broadcast::{self, Sender};
-use std::sync::OnceLock;
+use tokio::sync::oneshot;
+use tokio::time::{Duration, Instant};
 use serde::{Deserialize, Serialize};
-use toml;
 
-static STOP_SIGNAL_SENDER: OnceLock<Sender<()>> = OnceLock::new();
-
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+mod apps;
+mod block_page;
+mod blocker;
+mod categories;
+mod control;
+mod dns;
+mod dns_cache;
+mod lists;
+mod paths;
+mod schedule;
+mod signals;
+mod state;
+mod tamper;
+
+pub use blocker::{apply_blockers, build_blockers, check_resolution, hosts_entries, nftables_ruleset, revert_blockers, strip_managed_block, system_resolver, write_file_atomically, write_hosts_file, BlockPlan, Blocker, BrowserPolicyBlocker, DnsBlocker, HostResolver, HostsBlocker, NftablesBlocker, UnblockedHost};
+#[cfg(any(test, feature = "test-util"))]
+pub use blocker::MemoryBlocker;
+pub use categories::{select_categories, Selection};
+pub use apps::{thaw, AppBlockPolicy, AppEnforcement, AppOutcome, AppWatcher};
+pub use block_page::{render_block_page, BlockPageServer, DEFAULT_BLOCK_PAGE};
+use blocker::{has_managed_block, is_system_hosts, legacy_hosts_backup_file};
+pub use control::{ControlRequest, ControlResponse};
+use control::{send_request, ControlServer};
+pub use dns::{DnsFilter, DnsServer};
+pub use lists::{load_block_lists, load_list, parse_block_list, ListStatus, LoadedList};
+pub use dns_cache::{default_flush_commands, flush_dns_caches, report_flush, CommandRunner, FlushResult, SystemCommandRunner};
+pub use signals::{SignalPolicy, TerminationSignal};
+pub use schedule::Schedules;
+use signals::SignalListener;
+pub use state::{format_remaining, BlockedProcess, PauseState, Phase, SessionState};
+pub use tamper::TamperPolicy;
+use tamper::TamperWatcher;
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct Config {
     pub block_list: Option<Vec<String>>,
+    /// Block lists to merge into `block_list`: URLs, fetched and cached, or
+    /// local files, in hosts format or with one domain per line.
+    pub block_list_sources: Option<Vec<String>>,
+    /// Named groups of `block_list` entries, such as `social` or `news`, that a
+    /// session blocks in addition when chosen with `--block`.
+    pub categories: Option<BTreeMap<String, Vec<String>>>,
+    /// Times at which categories or domains are blocked, keyed by category
+    /// name or domain. A scheduled category is blocked during its windows
+    /// without `--block`; a scheduled domain only during its windows, unless
+    /// the session names it with `--block`.
+    pub schedules: Option<BTreeMap<String, Vec<ScheduleWindow>>>,
     pub app_block_list: Option<Vec<String>>,
+    /// What happens to blocked applications found running. Defaults to `"kill"`.
+    pub app_block_policy: Option<AppBlockPolicy>,
+    /// How often a session looks for relaunched blocked applications, such as
+    /// `"5s"` (the default).
+    pub app_watch_interval: Option<String>,
     pub whitelist: Option<Vec<String>>,
     pub pomodoro_defaults: Option<PomodoroDefaults>,
+    pub signal_policy: Option<SignalPolicy>,
+    /// Subdomains blocked alongside every plain domain in `block_list`.
+    /// Defaults to `["www"]`; an empty list blocks only the domains themselves.
+    pub subdomains: Option<Vec<String>>,
+    /// Addresses blocked hostnames are redirected to. Defaults to
+    /// `["127.0.0.1", "::1"]` so both IPv4 and IPv6 lookups are blocked.
+    pub redirect_addresses: Option<Vec<String>>,
+    pub dns: Option<DnsConfig>,
+    /// How `block_list` is enforced. Defaults to `["hosts"]`.
+    pub blocking_backends: Option<Vec<BlockingBackend>>,
+    pub nftables: Option<NftablesConfig>,
+    /// What happens when a block is removed or edited during a session.
+    /// Defaults to `"reapply"`.
+    pub tamper_policy: Option<TamperPolicy>,
+    pub dns_cache: Option<DnsCacheConfig>,
+    pub browser_policy: Option<BrowserPolicyConfig>,
+    pub block_page: Option<BlockPageConfig>,
+}
+
+/// A mechanism for blocking the websites in `block_list`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockingBackend {
+    /// Entries in a marker block of the hosts file.
+    Hosts,
+    /// A DNS forwarder run for the session that answers blocked names itself,
+    /// matching every subdomain as well.
+    Dns,
+    /// An nftables table rejecting traffic to the addresses the blocked
+    /// domains resolve to. Linux only.
+    Nftables,
+    /// Managed policies that make Firefox and Chromium-based browsers refuse
+    /// the blocked websites and turn off their DNS-over-HTTPS.
+    Browser,
+}
+
+/// Settings for the local resolver used by `--whitelist` mode and the `dns`
+/// blocking backend.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct DnsConfig {
+    /// Address to answer DNS queries on. Defaults to `127.0.0.1:53`.
+    pub listen: Option<String>,
+    /// Resolver that allowed queries are forwarded to. Defaults to the first
+    /// nameserver in /etc/resolv.conf, or 1.1.1.1.
+    pub upstream: Option<String>,
+}
+
+/// Settings for the `nftables` blocking backend.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct NftablesConfig {
+    /// Print the ruleset instead of installing it, so the backend can be
+    /// tried without root.
+    pub dry_run: Option<bool>,
+}
+
+/// Settings for clearing the system's DNS caches whenever websites are blocked
+/// or unblocked, so the change does not wait for cached answers to expire.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct DnsCacheConfig {
+    /// Whether to flush at all. Defaults to false.
+    pub flush: Option<bool>,
+    /// Commands to run, each with its arguments. Defaults to the usual ones for
+    /// the platform, such as `resolvectl flush-caches` and `nscd --invalidate=hosts` on Linux.
+    pub commands: Option<Vec<String>>,
+}
+
+/// Settings for the `browser` blocking backend.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct BrowserPolicyConfig {
+    /// Directory Firefox reads `policies.json` from. Defaults to
+    /// `/etc/firefox/policies` on Linux; an empty string leaves Firefox alone.
+    pub firefox_dir: Option<String>,
+    /// Managed policy directories of Chromium-based browsers. Defaults to the
+    /// ones of Google Chrome and Chromium on Linux.
+    pub chromium_dirs: Option<Vec<String>>,
+}
+
+/// A recurring time during which a scheduled category or domain is blocked.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct ScheduleWindow {
+    /// Days such as `"mon"`, ranges such as `"mon-fri"`, `"weekdays"` or
+    /// `"weekends"`. Defaults to every day.
+    pub days: Option<Vec<String>>,
+    /// Local time range such as `"09:00-17:00"`; `"22:00-02:00"` runs past
+    /// midnight. Defaults to the whole day.
+    pub hours: Option<String>,
+}
+
+/// Settings for the page shown when a browser opens a blocked website.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct BlockPageConfig {
+    /// Whether to serve the page during sessions. Defaults to false.
+    pub enabled: Option<bool>,
+    /// Port to serve it on at the redirect addresses. Defaults to 80.
+    pub port: Option<u16>,
+    /// HTML file to serve instead of the built-in page. `{{domain}}`,
+    /// `{{task}}`, `{{remaining}}` and `{{phase}}` are filled in.
+    pub template: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct PomodoroDefaults {
     pub pomodoro: String,
     pub r#break: String,
@@ -42,10 +185,17 @@ pub struct Cli {
 pub enum CliCommand {
     Start(StartArgs),
     Stop(StopArgs),
+    Status(StatusArgs),
+    Pause(PauseArgs),
+    Resume,
+    Extend(AdjustArgs),
+    Shorten(AdjustArgs),
+    Recover,
     Report,
+    Lists(ListsArgs),
 }
 
-#[derive(Parser)]
+#[derive(Parser, Clone)]
 pub struct StartArgs {
     #[clap(short, long, help = "Session duration (e.g., 25m, 1h, 90m, 1h30m)")]
     pub duration: String,
@@ -70,11 +220,50 @@ pub struct StartArgs {
 
     #[clap(long, help = "Number of pomodoro cycles before long break")]
     pub cycles: Option<u32>,
+
+    #[clap(long, value_delimiter = ',', conflicts_with = "whitelist", help = "Also block these categories from config.toml and domains (e.g., social,news,example.com)")]
+    pub block: Vec<String>,
+
+    #[clap(long, value_delimiter = ',', conflicts_with = "whitelist", help = "Keep these domains, or all domains of these categories, reachable for this session")]
+    pub allow: Vec<String>,
 }
 
 #[derive(Parser)]
 pub struct StopArgs {}
 
+#[derive(Parser)]
+pub struct PauseArgs {
+    #[clap(long, help = "Unblock websites and unmute audio until the session is resumed")]
+    pub lift_blocks: bool,
+}
+
+#[derive(Parser)]
+pub struct AdjustArgs {
+    #[clap(help = "How much time to add or remove (e.g., 10m, 1h)")]
+    pub duration: String,
+
+    #[clap(long, help = "Adjust the overall session duration, which caps the number of pomodoro cycles, instead of the current phase")]
+    pub session: bool,
+}
+
+#[derive(Parser)]
+pub struct ListsArgs {
+    #[clap(subcommand)]
+    pub command: ListsCommand,
+}
+
+#[derive(Subcommand)]
+pub enum ListsCommand {
+    /// Download the block lists in `block_list_sources` again if they changed
+    Update,
+}
+
+#[derive(Parser)]
+pub struct StatusArgs {
+    #[clap(long, help = "Print the session state as JSON")]
+    pub json: bool,
+}
+
 pub fn load_config() -> Config {
     if let Ok(content) = fs::read_to_string("config.toml") {
         if let Ok(config) = toml::from_str(&content) {
@@ -88,14 +277,10 @@ impl Default for Config {
     fn default() -> Self {
         Config {
             block_list: Some(vec![
-                "127.0.0.1 facebook.com".to_string(),
-                "127.0.0.1 www.facebook.com".to_string(),
-                "127.0.0.1 twitter.com".to_string(),
-                "127.0.0.1 www.twitter.com".to_string(),
-                "127.0.0.1 instagram.com".to_string(),
-                "127.0.0.1 www.instagram.com".to_string(),
-                "127.0.0.1 youtube.com".to_string(),
-                "127.0.0.1 www.youtube.com".to_string(),
+                "facebook.com".to_string(),
+                "twitter.com".to_string(),
+                "instagram.com".to_string(),
+                "youtube.com".to_string(),
             ]),
             app_block_list: Some(vec![
                 "slack.exe".to_string(),
@@ -108,6 +293,21 @@ impl Default for Config {
                 long_break: "15m".to_string(),
                 cycles: 4,
             }),
+            signal_policy: None,
+            app_block_policy: None,
+            app_watch_interval: None,
+            block_list_sources: None,
+            categories: None,
+            schedules: None,
+            subdomains: None,
+            redirect_addresses: None,
+            dns: None,
+            blocking_backends: None,
+            nftables: None,
+            tamper_policy: None,
+            dns_cache: None,
+            browser_policy: None,
+            block_page: None,
         }
     }
 }
@@ -154,176 +354,108 @@ fn get_user_hosts_path() -> PathBuf {
     }
 }
 
-pub async fn block_websites(args: &StartArgs, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
-    let hosts_path = get_hosts_path();
-    let is_system_hosts = hosts_path.to_string_lossy().contains("System32") || hosts_path.to_string_lossy().contains("/etc/");
-    
-    let original_content = if hosts_path.exists() {
-        fs::read_to_string(&hosts_path)?
-    } else {
-        String::new()
-    };
+/// Blocks what a session started with `args` would block right now with each
+/// of `blockers`, as created by [`build_blockers`]: `block_list`, the `--block`
+/// selection, the imported block lists and the open schedules, less `--allow`.
+/// Local block lists with relative paths are read from the directory of the
+/// configuration file. Returns the plan that was applied.
+pub async fn block_websites(blockers: &mut [Box<dyn Blocker>], args: &StartArgs, config: &Config) -> Result<BlockPlan, Box<dyn std::error::Error>> {
+    let selection = select_categories(config, &args.block, &args.allow)?;
+    let schedules = session_schedules(args, config, &selection)?;
+    let base_plan = session_base_plan(args, config, &selection, &schedules).await;
+    let plan = schedules.plan(&base_plan, &schedules.active(Local::now().naive_local()));
+    apply_blockers(blockers, &plan)?;
+    flush_dns_cache(config);
+    Ok(plan)
+}
 
-    // Create appropriate backup file
-    let backup_file = if is_system_hosts {
-        "hosts.backup"
-    } else {
-        "user_hosts.backup"
-    };
+// Whitelist mode ignores block_list, and with it everything scheduled
+fn session_schedules(args: &StartArgs, config: &Config, selection: &Selection) -> Result<Schedules, Box<dyn std::error::Error>> {
+    match args.whitelist {
+        true => Ok(Schedules::default()),
+        false => Schedules::from_config(config, selection),
+    }
+}
 
-    // Only create backup if it doesn't exist, to preserve the original clean state
-    if !std::path::Path::new(backup_file).exists() {
-        fs::write(backup_file, &original_content)?;
-        println!("Created hosts file backup at {}", backup_file);
-    } else {
-        println!("Using existing hosts file backup");
+// What a session blocks whether or not a schedule is open: `block_list`, the
+// `--block` selection and the imported block lists, less `--allow` and the
+// domains that are only blocked on schedule.
+async fn session_base_plan(args: &StartArgs, config: &Config, selection: &Selection, schedules: &Schedules) -> BlockPlan {
+    let mut plan = BlockPlan::from_config(config, args.whitelist);
+    plan.add_domains(selection.block.iter().cloned());
+    if let Some(sources) = config.block_list_sources.as_ref().filter(|_| !args.whitelist) {
+        let lists = load_block_lists(sources, &paths::config_base_dir()).await;
+        let added = plan.add_domains(lists.into_iter().flat_map(|list| list.domains));
+        println!("📋 Blocking {} more domains from block lists", added);
+    }
+    if plan.remove_domains(&selection.allow) > 0 {
+        println!("✅ Allowed for this session: {}", selection.allow.join(", "));
     }
+    plan.remove_domains(&schedules.scheduled_domains());
+    plan
+}
 
-    let mut new_content = original_content.clone();
-    
-    // Add header for user hosts file to explain its purpose
-    if !is_system_hosts && original_content.is_empty() {
-        new_content.push_str("# FlowMode user-level hosts file\n");
-        new_content.push_str("# This file blocks websites without requiring admin privileges\n");
-        new_content.push_str("# Note: This only works if you configure your system to use this as an additional hosts source\n\n");
-    }
-
-    if args.whitelist {
-        // Add a broad block for common social media and distraction sites
-        let broad_blocks = vec![
-            "127.0.0.1 facebook.com",
-            "127.0.0.1 www.facebook.com", 
-            "127.0.0.1 twitter.com",
-            "127.0.0.1 www.twitter.com",
-            "127.0.0.1 instagram.com", 
-            "127.0.0.1 www.instagram.com",
-            "127.0.0.1 youtube.com",
-            "127.0.0.1 www.youtube.com",
-            "127.0.0.1 reddit.com",
-            "127.0.0.1 www.reddit.com",
-            "127.0.0.1 tiktok.com",
-            "127.0.0.1 www.tiktok.com",
-        ];
-        
-        for block in broad_blocks {
-            if !new_content.contains(block) {
-                new_content.push_str("\n");
-                new_content.push_str(block);
-            }
-        }
-        
-        // Remove whitelist domains from blocks if they exist
-        if let Some(whitelist) = &config.whitelist {
-            for domain in whitelist {
-                // Remove any blocking entries for whitelisted domains
-                let patterns_to_remove = vec![
-                    format!("127.0.0.1 {}", domain),
-                    format!("127.0.0.1 www.{}", domain),
-                ];
-                
-                for pattern in patterns_to_remove {
-                    new_content = new_content.replace(&pattern, "");
-                }
-                println!("Whitelisted domain: {}", domain);
+/// Handles the blocked applications running now according to
+/// `app_block_policy` and reports what was done to each. Processes with the
+/// `Frozen` outcome stay stopped until they are passed to [`thaw`].
+pub async fn block_applications(config: &Config) -> Result<Vec<AppEnforcement>, Box<dyn std::error::Error>> {
+    let mut watcher = AppWatcher::from_config(config)?;
+    Ok(close_applications(&mut watcher))
+}
+
+// Handles the blocked applications running now and reports what was done
+fn close_applications(watcher: &mut AppWatcher) -> Vec<AppEnforcement> {
+    let enforcements = watcher.enforce();
+    for enforcement in &enforcements {
+        match enforcement.outcome {
+            AppOutcome::Killed => println!("Successfully killed process: {} (PID: {})", enforcement.name, enforcement.pid),
+            AppOutcome::KillFailed => {
+                eprintln!("Failed to kill process: {} (PID: {}) - may require elevated privileges", enforcement.name, enforcement.pid)
             }
+            AppOutcome::Frozen => println!("Froze process: {} (PID: {}) until the session ends", enforcement.name, enforcement.pid),
+            AppOutcome::FreezeFailed => eprintln!("Failed to freeze process: {} (PID: {})", enforcement.name, enforcement.pid),
+            AppOutcome::Running => println!("Blocked application is running: {} (PID: {})", enforcement.name, enforcement.pid),
         }
-    } else {
-        if let Some(block_list) = &config.block_list {
-            for site in block_list {
-                if !new_content.contains(site) {
-                    new_content.push_str("\n");
-                    new_content.push_str(site);
-                }
-            }
+    }
+    for app_name in watcher.apps() {
+        if !enforcements.iter().any(|enforcement| enforcement.name == *app_name) {
+            println!("No instances of {} found running under current user", app_name);
         }
     }
-
-    fs::write(&hosts_path, new_content)?;
-
-    Ok(())
+    enforcements
 }
 
-pub async fn block_applications(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(app_list) = &config.app_block_list {
-        let mut system = System::new_all();
-        system.refresh_all();
-        
-        let _current_user = std::env::var("USERNAME").or_else(|_| std::env::var("USER")).unwrap_or_else(|_| "unknown".to_string());
-
-        for app_name in app_list {
-            let mut killed_any = false;
-            for (pid, process) in system.processes() {
-                if process.name().to_string_lossy() == *app_name {
-                    // Try to get process owner, only kill if it's the current user's process or we can't determine ownership
-                    let can_kill = if let Some(_uid) = process.user_id() {
-                        // On Unix-like systems, check if it's our UID
-                        #[cfg(unix)]
-                        {
-                            use nix::unistd::getuid;
-                            _uid == &getuid()
-                        }
-                        #[cfg(not(unix))]
-                        {
-                            true // On Windows, we'll try and let the OS decide
-                        }
-                    } else {
-                        true // If we can't determine ownership, try anyway
-                    };
-                    
-                    if can_kill {
-                        if process.kill() {
-                            println!("Successfully killed process: {} (PID: {})", app_name, pid);
-                            killed_any = true;
-                        } else {
-                            eprintln!("Failed to kill process: {} (PID: {}) - may require elevated privileges", app_name, pid);
-                        }
-                    } else {
-                        println!("Skipped process {} (PID: {}) - not owned by current user", app_name, pid);
-                    }
-                }
-            }
-            if !killed_any {
-                println!("No instances of {} found running under current user", app_name);
-            }
-        }
+/// Lifts the blocks of every one of `blockers`, even if some of them fail,
+/// and reports the failures together.
+pub async fn unblock_websites(blockers: &mut [Box<dyn Blocker>], config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let failures = revert_blockers(blockers);
+    flush_dns_cache(config);
+    if !failures.is_empty() {
+        return Err(failures.join("; ").into());
     }
     Ok(())
 }
 
-pub async fn unblock_websites() -> Result<(), Box<dyn std::error::Error>> {
-    let hosts_path = get_hosts_path();
-    let is_system_hosts = hosts_path.to_string_lossy().contains("System32") || hosts_path.to_string_lossy().contains("/etc/");
-    
-    let backup_file = if is_system_hosts {
-        "hosts.backup"
-    } else {
-        "user_hosts.backup"
+// Clears the DNS caches if `[dns_cache]` asks for it, so that blocking and
+// unblocking take effect right away.
+fn flush_dns_cache(config: &Config) {
+    let Some(settings) = config.dns_cache.as_ref().filter(|settings| settings.flush.unwrap_or(false)) else {
+        return;
     };
+    let commands = settings.commands.clone().unwrap_or_else(default_flush_commands);
+    report_flush(&flush_dns_caches(&commands, &SystemCommandRunner));
+}
 
-    if let Ok(backup_content) = fs::read_to_string(backup_file) {
-        fs::write(&hosts_path, backup_content)?;
-        if let Err(e) = fs::remove_file(backup_file) {
-            eprintln!("Warning: Failed to remove backup file: {}", e);
-        }
-        println!("Successfully restored hosts file from backup");
-    } else {
-        // For user hosts, just delete the file if no backup exists
-        if !is_system_hosts && hosts_path.exists() {
-            if let Err(e) = fs::remove_file(&hosts_path) {
-                eprintln!("Warning: Failed to remove user hosts file: {}", e);
-            } else {
-                println!("Removed user hosts file");
-            }
+// Lets the applications frozen by the session continue. Killed applications
+// stay closed.
+async fn unblock_applications(frozen: &[BlockedProcess]) -> Result<(), Box<dyn std::error::Error>> {
+    for process in frozen {
+        if thaw(process) {
+            println!("▶️  Resumed {} (PID {})", process.name, process.pid);
         } else {
-            println!("No backup file found, hosts file not modified");
+            println!("{} (PID {}) is no longer running", process.name, process.pid);
         }
     }
-
-    Ok(())
-}
-
-async fn unblock_applications() -> Result<(), Box<dyn std::error::Error>> {
-    // Nothing to do here for now, as we are just killing the processes.
     Ok(())
 }
 
@@ -334,19 +466,17 @@ async fn mute_notifications() -> Result<(), Box<dyn std::error::Error>> {
         let mut success = false;
         
         // Try PowerShell user-level volume control (Windows 10+)
-        match Command::new("powershell")
+        if Command::new("powershell")
             .arg("-Command")
             .arg("(New-Object -ComObject WScript.Shell).SendKeys([char]173)") // Volume down key
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .status()
             .await
+            .is_ok()
         {
-            Ok(_) => {
-                println!("Audio muted using user-level control (Windows)");
-                success = true;
-            }
-            Err(_) => {}
+            println!("Audio muted using user-level control (Windows)");
+            success = true;
         }
         
         // Fallback to nircmd if available
@@ -397,7 +527,7 @@ async fn mute_notifications() -> Result<(), Box<dyn std::error::Error>> {
         let mut success = false;
         
         // Try pactl for PulseAudio (user-level)
-        match Command::new("pactl")
+        if Command::new("pactl")
             .arg("set-sink-mute")
             .arg("@DEFAULT_SINK@")
             .arg("1")
@@ -405,17 +535,15 @@ async fn mute_notifications() -> Result<(), Box<dyn std::error::Error>> {
             .stderr(Stdio::null())
             .status()
             .await
+            .is_ok()
         {
-            Ok(_) => {
-                println!("Audio muted using pactl (Linux)");
-                success = true;
-            }
-            Err(_) => {}
+            println!("Audio muted using pactl (Linux)");
+            success = true;
         }
         
         // Fallback to amixer
-        if !success {
-            match Command::new("amixer")
+        if !success
+            && Command::new("amixer")
                 .arg("sset")
                 .arg("Master")
                 .arg("mute")
@@ -423,13 +551,10 @@ async fn mute_notifications() -> Result<(), Box<dyn std::error::Error>> {
                 .stderr(Stdio::null())
                 .status()
                 .await
-            {
-                Ok(_) => {
-                    println!("Audio muted using amixer (Linux)");
-                    success = true;
-                }
-                Err(_) => {}
-            }
+                .is_ok()
+        {
+            println!("Audio muted using amixer (Linux)");
+            success = true;
         }
         
         if !success {
@@ -447,19 +572,17 @@ async fn unmute_notifications() -> Result<(), Box<dyn std::error::Error>> {
         let mut success = false;
         
         // Try PowerShell user-level volume control (Windows 10+)
-        match Command::new("powershell")
+        if Command::new("powershell")
             .arg("-Command")
             .arg("(New-Object -ComObject WScript.Shell).SendKeys([char]175)") // Volume up key to unmute
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .status()
             .await
+            .is_ok()
         {
-            Ok(_) => {
-                println!("Audio unmuted using user-level control (Windows)");
-                success = true;
-            }
-            Err(_) => {}
+            println!("Audio unmuted using user-level control (Windows)");
+            success = true;
         }
         
         // Fallback to nircmd if available
@@ -510,7 +633,7 @@ async fn unmute_notifications() -> Result<(), Box<dyn std::error::Error>> {
         let mut success = false;
         
         // Try pactl for PulseAudio (user-level)
-        match Command::new("pactl")
+        if Command::new("pactl")
             .arg("set-sink-mute")
             .arg("@DEFAULT_SINK@")
             .arg("0")
@@ -518,17 +641,15 @@ async fn unmute_notifications() -> Result<(), Box<dyn std::error::Error>> {
             .stderr(Stdio::null())
             .status()
             .await
+            .is_ok()
         {
-            Ok(_) => {
-                println!("Audio unmuted using pactl (Linux)");
-                success = true;
-            }
-            Err(_) => {}
+            println!("Audio unmuted using pactl (Linux)");
+            success = true;
         }
         
         // Fallback to amixer
-        if !success {
-            match Command::new("amixer")
+        if !success
+            && Command::new("amixer")
                 .arg("sset")
                 .arg("Master")
                 .arg("unmute")
@@ -536,13 +657,10 @@ async fn unmute_notifications() -> Result<(), Box<dyn std::error::Error>> {
                 .stderr(Stdio::null())
                 .status()
                 .await
-            {
-                Ok(_) => {
-                    println!("Audio unmuted using amixer (Linux)");
-                    success = true;
-                }
-                Err(_) => {}
-            }
+                .is_ok()
+        {
+            println!("Audio unmuted using amixer (Linux)");
+            success = true;
         }
         
         if !success {
@@ -569,6 +687,41 @@ pub async fn post_to_slack(url: &str, message: &str) -> Result<(), Box<dyn std::
     Ok(())
 }
 
+// Lookups normally answer in milliseconds; a lost packet makes the system
+// resolver retry after seconds, which should not hold up the session start
+const RESOLUTION_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Tells the user right away when blocked domains still resolve, e.g. because
+// the OS does not read the hosts file that was written or uses another DNS server.
+async fn report_resolution(plan: &BlockPlan) {
+    let plan = plan.clone();
+    let (sender, check) = oneshot::channel();
+    // A detached thread, so a lookup still hanging does not delay exiting either
+    std::thread::spawn(move || {
+        let _ = sender.send(check_resolution(&plan, system_resolver));
+    });
+    let unblocked = match tokio::time::timeout(RESOLUTION_CHECK_TIMEOUT, check).await {
+        Ok(Ok(unblocked)) => unblocked,
+        Ok(Err(_)) => return,
+        Err(_) => {
+            println!("⚠️  Could not check whether blocking is in effect: DNS lookups did not answer in time");
+            return;
+        }
+    };
+    if unblocked.is_empty() {
+        println!("🔍 Checked: blocked domains no longer resolve to their real addresses");
+        return;
+    }
+
+    println!("⚠️  Blocking is not in effect for {} domains, they still resolve to real addresses:", unblocked.len());
+    for host in &unblocked {
+        let addresses: Vec<String> = host.addresses.iter().map(std::net::IpAddr::to_string).collect();
+        println!("   • {} → {}", host.host, addresses.join(", "));
+    }
+    println!("   Your system may not be reading the hosts file flowmode wrote, or may be using a different DNS server.");
+    println!("   Lookups cached by the browser or the OS can also take a minute to expire.");
+}
+
 fn print_user_hosts_guidance(hosts_path: &std::path::Path) {
     println!("\n📋 FlowMode is using a user-level hosts file for website blocking.");
     println!("   Location: {}", hosts_path.display());
@@ -577,9 +730,6 @@ fn print_user_hosts_guidance(hosts_path: &std::path::Path) {
     if cfg!(target_os = "windows") {
         println!("   • Configure your DNS server to use this file as an additional hosts source");
         println!("   • Or copy the contents to C:\\Windows\\System32\\drivers\\etc\\hosts (requires admin)");
-    } else if cfg!(target_os = "macos") {
-        println!("   • Copy the contents to /etc/hosts (requires sudo)");
-        println!("   • Or configure your DNS resolver to use this file");
     } else {
         println!("   • Copy the contents to /etc/hosts (requires sudo)");
         println!("   • Or configure your DNS resolver to use this file");
@@ -589,58 +739,470 @@ fn print_user_hosts_guidance(hosts_path: &std::path::Path) {
     println!("   • FlowMode will still provide focus tools and app blocking without admin rights\n");
 }
 
-async fn start_flow_mode(args: StartArgs) -> Result<(), Box<dyn std::error::Error>> {
-    let config = load_config();
+// A PID file can outlive a reboot, after which its PID may belong to an
+// unrelated process, so the process name must match this executable as well.
+fn is_session_running(pid: u32) -> bool {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::Some(&[pid]));
+    let Some(process) = system.process(pid) else {
+        return false;
+    };
+    match std::env::current_exe().ok().and_then(|exe| exe.file_name().map(|name| name.to_os_string())) {
+        Some(exe_name) => process.name() == exe_name,
+        None => true,
+    }
+}
 
-    println!("🚀 Starting Flow Mode session...");
-    
-    // Validate duration early to catch errors before any setup
-    let _duration_check = humantime::parse_duration(&args.duration)
-        .map_err(|e| format!("Invalid duration '{}': {}. Use format like '25m', '1h', '30s', etc.", args.duration, e))?;
-    
-    println!("📵 Blocking distracting websites...");
-    let hosts_path = get_hosts_path();
-    let is_system_hosts = hosts_path.to_string_lossy().contains("System32") || hosts_path.to_string_lossy().contains("/etc/");
-    
-    block_websites(&args, &config).await?;
-    
-    // Show guidance if using user-level hosts
-    if !is_system_hosts {
-        print_user_hosts_guidance(&hosts_path);
+fn read_session_pid() -> Option<u32> {
+    fs::read_to_string(paths::pid_file()).ok()?.trim().parse().ok()
+}
+
+#[derive(Debug, PartialEq)]
+enum PhaseOutcome {
+    Completed,
+    Stopped,
+}
+
+// A session that ended without its teardown leaves a PID file, state file,
+// hosts entries or an open record in the log behind.
+fn has_session_leftovers() -> bool {
+    if read_session_pid().is_some_and(is_session_running) {
+        return false;
     }
-    
-    println!("🔪 Closing distracting applications...");
-    block_applications(&config).await?;
-    
-    println!("🔇 Muting notifications...");
-    mute_notifications().await?;
+    read_session_pid().is_some()
+        || paths::state_file().exists()
+        || fs::read_to_string(get_hosts_path()).is_ok_and(|content| has_managed_block(&content))
+        || legacy_hosts_backup_file(&get_hosts_path()).exists()
+        || log_has_open_record()
+}
 
-    if let Some(url) = &args.slack_webhook_url {
-        if let Err(e) = post_to_slack(url, "In flow mode, will reply later.").await {
-            eprintln!("Warning: Failed to post to Slack: {}", e);
-        }
+fn log_has_open_record() -> bool {
+    fs::read_to_string(paths::log_file()).is_ok_and(|content| !content.is_empty() && !content.ends_with('\n'))
+}
+
+// Restores what an abnormally terminated session left behind and closes its
+// log record with an "aborted" marker.
+async fn recover_session() -> Result<(), Box<dyn std::error::Error>> {
+    let state = SessionState::load();
+
+    // First, so frozen apps continue even if a block cannot be lifted
+    if let Some(state) = state.as_ref().filter(|state| !state.apps_frozen.is_empty()) {
+        println!("▶️  Resuming frozen applications...");
+        unblock_applications(&state.apps_frozen).await?;
+    }
+    // The hosts file is always cleaned up, since an older version may have
+    // written to it whatever the configuration says now
+    println!("📵 Restoring hosts file...");
+    let mut blockers: Vec<Box<dyn Blocker>> = vec![Box::new(HostsBlocker::new(get_hosts_path()))];
+    let used_nftables = state.as_ref().is_some_and(|state| state.blockers.iter().any(|blocker| blocker.starts_with("nftables")));
+    let config = load_config();
+    if used_nftables || blocker::uses_backend(&config, BlockingBackend::Nftables) {
+        println!("🧱 Removing firewall rules...");
+        blockers.push(Box::new(NftablesBlocker::from_config(&config)));
+    }
+    let used_browser_policies = state.as_ref().is_some_and(|state| state.blockers.iter().any(|blocker| blocker.starts_with("browser policies")));
+    if used_browser_policies || blocker::uses_backend(&config, BlockingBackend::Browser) {
+        println!("🧭 Removing browser policies...");
+        blockers.push(Box::new(BrowserPolicyBlocker::from_config(&config)));
+    }
+    unblock_websites(&mut blockers, &config).await?;
+    println!("🔇 Restoring audio...");
+    unmute_notifications().await?;
+
+    if log_has_open_record() {
+        // The last state update is the best estimate of when the session died
+        let end_time = fs::metadata(paths::state_file())
+            .and_then(|metadata| metadata.modified())
+            .map(DateTime::<Local>::from)
+            .unwrap_or_else(|_| Local::now());
+        let mut file = fs::OpenOptions::new().append(true).open(paths::log_file())?;
+        writeln!(file, "{},0,aborted", end_time.to_rfc3339())?;
+        println!("📝 Closed the unfinished session record in {}", paths::log_file().display());
+    }
+    if let Some(state) = &state {
+        record_event(&state.started_at, "aborted", "recovered after abnormal exit")?;
     }
 
-    println!("✅ Flow mode activated! Focus time begins now.");
-    if let Some(ref task) = args.task {
-        println!("📝 Working on: {}", task);
+    SessionState::remove();
+    let _ = fs::remove_file(control::control_endpoint_path());
+    let pid_file = paths::pid_file();
+    if pid_file.exists() {
+        fs::remove_file(&pid_file)?;
     }
 
-    let pid = std::process::id();
-    fs::write("flowmode.pid", pid.to_string())?;
+    println!("✅ Recovered from the previous session.");
+    Ok(())
+}
 
-    // Always log session start, with task name or "No task specified"
-    let task_name = args.task.as_deref().unwrap_or("No task specified");
+// Appends a timestamped event for the session that started at `session_start`.
+fn record_event(session_start: &DateTime<Local>, event: &str, detail: &str) -> Result<(), Box<dyn std::error::Error>> {
     let mut file = fs::OpenOptions::new()
         .create(true)
         .append(true)
-        .open("log.csv")?;
-    let start_time = Local::now();
-    write!(file, "{},{},", task_name, start_time.to_rfc3339())?;
-    println!("Logging task: {}", task_name);
+        .open(paths::events_file())?;
+    writeln!(file, "{},{},{},{}", session_start.to_rfc3339(), Local::now().to_rfc3339(), event, detail)?;
+    Ok(())
+}
+
+const INTERRUPT_CONFIRM_WINDOW: Duration = Duration::from_secs(10);
+// The wall clock can jump (suspend, daylight saving time) while a timer waits,
+// so schedules are looked at again at least this often
+const MAX_SCHEDULE_WAIT: Duration = Duration::from_secs(300);
+
+struct CyclePlan {
+    budget: Duration,
+    cycle_duration: Duration,
+    cycles: u32,
+}
+
+// State owned by the running session: its blocking backends, the control channel it listens on, the
+// snapshot published for `flowmode status`, pause bookkeeping and, once
+// someone asked it to stop, the reply to send after teardown.
+struct Session {
+    config: Config,
+    control: ControlServer,
+    signals: SignalListener,
+    // The website blocking backends and what they block
+    blockers: Vec<Box<dyn Blocker>>,
+    plan: BlockPlan,
+    // What is blocked all session; `plan` adds the open schedules to it
+    base_plan: BlockPlan,
+    schedules: Schedules,
+    active_schedules: Vec<String>,
+    // Says when to check that the blocks are still in place
+    tamper: TamperWatcher,
+    // Per blocker, whether its failed verification was already reported
+    tampered: Vec<bool>,
+    // Closes blocked applications relaunched during the session
+    apps: AppWatcher,
+    // Answers browsers sent to a redirect address until the session ends
+    _block_page: Option<BlockPageServer>,
+    // Set by the first Ctrl+C in strict mode; a second one inside the window ends the session
+    interrupted_at: Option<Instant>,
+    state: SessionState,
+    // Only set for pomodoro sessions, where the overall duration caps the cycle count
+    cycle_plan: Option<CyclePlan>,
+    // When the current pause began and how much of the phase was left at that point
+    pause: Option<(Instant, Duration)>,
+    paused_total: Duration,
+    stop_reply: Option<oneshot::Sender<ControlResponse>>,
+}
+
+impl Session {
+    async fn run_phase(&mut self, phase: Phase, cycle: u32, duration: Duration) -> PhaseOutcome {
+        let mut deadline = Instant::now() + duration;
+        self.state.phase = phase;
+        self.state.cycle = cycle;
+        self.set_deadline(deadline);
+
+        loop {
+            let schedule_wait = self.schedule_wait();
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline), if self.pause.is_none() => return PhaseOutcome::Completed,
+                Some(message) = self.control.recv() => {
+                    let response = match message.request {
+                        ControlRequest::Stop => {
+                            self.stop_reply = Some(message.reply);
+                            return PhaseOutcome::Stopped;
+                        }
+                        ControlRequest::Pause { lift_blocks } => self.pause(deadline, lift_blocks).await,
+                        ControlRequest::Adjust { seconds, whole_session } => {
+                            if whole_session && self.cycle_plan.is_some() {
+                                self.adjust_budget(seconds)
+                            } else {
+                                deadline = self.adjust_phase(deadline, seconds);
+                                let remaining = format_remaining(self.state.remaining());
+                                println!("⏱️  {} left in this phase", remaining);
+                                ControlResponse::ok(format!("{} left in this phase", remaining))
+                            }
+                        }
+                        ControlRequest::Resume => match self.resume().await {
+                            Ok(remaining) => {
+                                deadline = Instant::now() + remaining;
+                                self.set_deadline(deadline);
+                                ControlResponse::ok(format!("Session resumed with {} left in this phase", format_remaining(self.state.remaining())))
+                            }
+                            Err(message) => ControlResponse::error(message),
+                        },
+                    };
+                    let _ = message.reply.send(response);
+                }
+                _ = self.tamper.next_check() => self.check_blocks(),
+                _ = self.apps.tick(), if !self.apps.is_empty() => self.enforce_apps(),
+                _ = tokio::time::sleep(schedule_wait), if !self.schedules.is_empty() => self.update_schedules(),
+                signal = self.signals.recv() => {
+                    if self.should_stop_on(signal) {
+                        println!("\n🛑 Received {}, ending the session...", signal);
+                        self.log_event("signal", &signal.to_string());
+                        return PhaseOutcome::Stopped;
+                    }
+                }
+            }
+        }
+    }
+
+    // Verifies every blocker and handles the ones whose block was removed or
+    // edited according to `tamper_policy`.
+    fn check_blocks(&mut self) {
+        // Lifted blocks are supposed to be gone
+        if self.state.paused.as_ref().is_some_and(|pause| pause.blocks_lifted) {
+            return;
+        }
+
+        let policy = self.config.tamper_policy.unwrap_or_default();
+        let mut tampered = Vec::new();
+        for (blocker, reported) in self.blockers.iter_mut().zip(self.tampered.iter_mut()) {
+            let Err(e) = blocker.verify(&self.plan) else {
+                *reported = false;
+                continue;
+            };
+            // A block left tampered with is reported once, not on every check
+            let first = !std::mem::replace(reported, true);
+            let description = blocker.describe();
+            if first {
+                println!("⚠️  Blocking with {} was tampered with: {}", description, e);
+            }
+            let detail = match policy {
+                TamperPolicy::Reapply => match blocker.apply(&self.plan) {
+                    Ok(()) => {
+                        *reported = false;
+                        println!("🔒 Blocking with {} again", description);
+                        Some(format!("{} reapplied", description))
+                    }
+                    Err(e) => first.then(|| {
+                        eprintln!("Warning: Failed to block websites with {} again: {}", description, e);
+                        format!("{} could not be reapplied", description)
+                    }),
+                },
+                TamperPolicy::Log => first.then_some(description),
+            };
+            tampered.extend(detail);
+        }
+        for detail in tampered {
+            self.log_event("tamper", &detail);
+        }
+    }
+
+    // Handles blocked applications launched since the last look. Lifted blocks
+    // leave applications alone too.
+    fn enforce_apps(&mut self) {
+        if self.state.paused.as_ref().is_some_and(|pause| pause.blocks_lifted) {
+            return;
+        }
+        let enforcements = self.apps.enforce();
+        for enforcement in &enforcements {
+            match enforcement.outcome {
+                AppOutcome::Killed => {
+                    println!("🔪 Closed {} (PID {}) again", enforcement.name, enforcement.pid);
+                    self.state.apps_killed.push(enforcement.process());
+                }
+                AppOutcome::KillFailed => eprintln!("Warning: Failed to close {} (PID {})", enforcement.name, enforcement.pid),
+                AppOutcome::Frozen => {
+                    println!("🧊 Froze {} (PID {})", enforcement.name, enforcement.pid);
+                    if !self.state.apps_frozen.contains(&enforcement.process()) {
+                        self.state.apps_frozen.push(enforcement.process());
+                    }
+                }
+                AppOutcome::FreezeFailed => eprintln!("Warning: Failed to freeze {} (PID {})", enforcement.name, enforcement.pid),
+                AppOutcome::Running => println!("⚠️  {} (PID {}) is running", enforcement.name, enforcement.pid),
+            }
+            self.log_event("app", &enforcement.to_string());
+        }
+        if enforcements.iter().any(|enforcement| matches!(enforcement.outcome, AppOutcome::Killed | AppOutcome::Frozen)) {
+            self.save_state();
+        }
+    }
 
-    let (tx, mut rx) = broadcast::channel(1);
-    STOP_SIGNAL_SENDER.set(tx).unwrap();
+    // How long until a schedule window may open or close
+    fn schedule_wait(&self) -> Duration {
+        let now = Local::now().naive_local();
+        let until = self.schedules.next_change(now).and_then(|at| (at - now).to_std().ok());
+        until.unwrap_or(MAX_SCHEDULE_WAIT).min(MAX_SCHEDULE_WAIT)
+    }
+
+    // Blocks the schedules whose windows opened and unblocks the ones that closed
+    fn update_schedules(&mut self) {
+        let active = self.schedules.active(Local::now().naive_local());
+        if active == self.active_schedules {
+            return;
+        }
+        for name in active.iter().filter(|name| !self.active_schedules.contains(name)) {
+            println!("🕘 {} is blocked on schedule now", name);
+            self.log_event("schedule", &format!("{} blocked", name));
+        }
+        for name in self.active_schedules.iter().filter(|name| !active.contains(name)) {
+            println!("🕘 {} is no longer blocked on schedule", name);
+            self.log_event("schedule", &format!("{} unblocked", name));
+        }
+        self.plan = self.schedules.plan(&self.base_plan, &active);
+        self.active_schedules = active;
+
+        // Lifted blocks come back with the new plan on resume
+        if self.state.paused.as_ref().is_some_and(|pause| pause.blocks_lifted) {
+            return;
+        }
+        for blocker in self.blockers.iter_mut() {
+            if let Err(e) = blocker.update(&self.plan) {
+                eprintln!("Warning: Failed to update blocking with {}: {}", blocker.describe(), e);
+            }
+        }
+        flush_dns_cache(&self.config);
+    }
+
+    // SIGTERM and SIGHUP always end the session: there is nobody left to ask
+    // when the terminal closed or the system is shutting down.
+    fn should_stop_on(&mut self, signal: TerminationSignal) -> bool {
+        let policy = self.config.signal_policy.unwrap_or_default();
+        if signal != TerminationSignal::Interrupt || policy == SignalPolicy::Immediate {
+            return true;
+        }
+
+        if self.interrupted_at.is_some_and(|at| at.elapsed() < INTERRUPT_CONFIRM_WINDOW) {
+            return true;
+        }
+        self.interrupted_at = Some(Instant::now());
+        println!(
+            "\n⚠️  Strict mode: are you sure you want to end this focus session? Press Ctrl+C again within {} seconds to confirm.",
+            INTERRUPT_CONFIRM_WINDOW.as_secs()
+        );
+        false
+    }
+
+    // Moves the end of the current phase by `seconds` and returns the new deadline.
+    // A paused phase has its frozen remaining time adjusted instead.
+    fn adjust_phase(&mut self, deadline: Instant, seconds: i64) -> Instant {
+        let delta = Duration::from_secs(seconds.unsigned_abs());
+        let now = Instant::now();
+        if let (Some((_, remaining)), Some(pause)) = (self.pause.as_mut(), self.state.paused.as_mut()) {
+            *remaining = if seconds >= 0 { *remaining + delta } else { remaining.saturating_sub(delta) };
+            pause.remaining_secs = remaining.as_secs();
+        }
+
+        let deadline = if seconds >= 0 { deadline + delta } else { deadline.checked_sub(delta).unwrap_or(now).max(now) };
+        self.set_deadline(deadline);
+        self.log_event(if seconds >= 0 { "extend" } else { "shorten" }, &format!("phase {:+}s", seconds));
+        deadline
+    }
+
+    // Changes the overall session duration, which caps how many pomodoro cycles run.
+    fn adjust_budget(&mut self, seconds: i64) -> ControlResponse {
+        let Some(plan) = self.cycle_plan.as_mut() else {
+            return ControlResponse::error("Session has no cycle budget");
+        };
+        let delta = Duration::from_secs(seconds.unsigned_abs());
+        plan.budget = if seconds >= 0 { plan.budget + delta } else { plan.budget.saturating_sub(delta) };
+
+        self.update_total_cycles();
+        self.save_state();
+        self.log_event(if seconds >= 0 { "extend" } else { "shorten" }, &format!("session {:+}s", seconds));
+
+        let message = format!("Session now runs {} pomodoro cycle(s)", self.state.total_cycles);
+        println!("⏱️  {}", message);
+        ControlResponse::ok(message)
+    }
+
+    fn update_total_cycles(&mut self) {
+        if let Some(plan) = &self.cycle_plan {
+            let max_sessions = (plan.budget.as_secs() / plan.cycle_duration.as_secs().max(1)).max(1) as u32;
+            // Never drop below the cycle that is already running
+            self.state.total_cycles = plan.cycles.min(max_sessions).max(self.state.cycle);
+        }
+    }
+
+    fn set_deadline(&mut self, deadline: Instant) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        self.state.phase_deadline = Local::now() + chrono::Duration::from_std(remaining).unwrap_or(chrono::Duration::MAX);
+        self.save_state();
+    }
+
+    fn save_state(&self) {
+        if let Err(e) = self.state.save() {
+            eprintln!("Warning: Failed to update session state: {}", e);
+        }
+    }
+
+    fn log_event(&self, event: &str, detail: &str) {
+        if let Err(e) = record_event(&self.state.started_at, event, detail) {
+            eprintln!("Warning: Failed to record {} event: {}", event, e);
+        }
+    }
+
+    async fn pause(&mut self, deadline: Instant, lift_blocks: bool) -> ControlResponse {
+        if self.pause.is_some() {
+            return ControlResponse::error("Session is already paused");
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if lift_blocks {
+            for failure in revert_blockers(&mut self.blockers) {
+                eprintln!("Warning: {}", failure);
+            }
+            flush_dns_cache(&self.config);
+            if let Err(e) = unmute_notifications().await {
+                eprintln!("Warning: Failed to unmute notifications for the pause: {}", e);
+            }
+        }
+
+        self.pause = Some((Instant::now(), remaining));
+        self.state.paused = Some(PauseState {
+            since: Local::now(),
+            remaining_secs: remaining.as_secs(),
+            blocks_lifted: lift_blocks,
+        });
+        self.save_state();
+        self.log_event("pause", if lift_blocks { "blocks lifted" } else { "" });
+
+        let remaining = format_remaining(self.state.remaining());
+        println!("⏸️  Session paused with {} left in this phase", remaining);
+        ControlResponse::ok(format!("Session paused with {} left in this phase", remaining))
+    }
+
+    // Ends the current pause and returns the time that was left in the phase.
+    async fn resume(&mut self) -> Result<Duration, String> {
+        let (since, remaining) = self.pause.take().ok_or("Session is not paused")?;
+        let pause = self.state.paused.take();
+
+        if pause.is_some_and(|p| p.blocks_lifted) {
+            if let Err(e) = apply_blockers(&mut self.blockers, &self.plan) {
+                eprintln!("Warning: {}", e);
+            }
+            flush_dns_cache(&self.config);
+            if let Err(e) = mute_notifications().await {
+                eprintln!("Warning: Failed to mute notifications again: {}", e);
+            }
+        }
+
+        let paused_for = since.elapsed();
+        self.paused_total += paused_for;
+        self.log_event("resume", &format!("paused for {}s", paused_for.as_secs()));
+        println!("▶️  Session resumed");
+        Ok(remaining)
+    }
+
+    async fn finish(mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let paused_total = self.paused_total + self.pause.map_or(Duration::ZERO, |(since, _)| since.elapsed());
+        let result = end_session(&mut self.blockers, &self.state.apps_frozen, &self.config, paused_total).await;
+        if let Some(reply) = self.stop_reply {
+            let _ = reply.send(match &result {
+                Ok(()) => ControlResponse::ok("Flow mode session stopped"),
+                Err(e) => ControlResponse::error(format!("Session stopped with errors: {}", e)),
+            });
+        }
+        self.control.close().await;
+        result
+    }
+}
+
+async fn start_flow_mode(args: StartArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config();
+
+    println!("🚀 Starting Flow Mode session...");
+    
+    // Validate every duration before any setup, so a typo cannot leave
+    // websites blocked or apps frozen behind
+    let session_duration = humantime::parse_duration(&args.duration)
+        .map_err(|e| format!("Invalid duration '{}': {}. Use format like '25m', '1h', '30s', etc.", args.duration, e))?;
 
     let pomodoro_duration = if let Some(ref d) = args.pomodoro {
         humantime::parse_duration(d).map_err(|e| format!("Invalid pomodoro duration '{}': {}. Use format like '25m', '1h', etc.", d, e))?
@@ -674,30 +1236,174 @@ async fn start_flow_mode(args: StartArgs) -> Result<(), Box<dyn std::error::Erro
         4
     };
 
+    let selection = select_categories(&config, &args.block, &args.allow)?;
+    let mut apps = AppWatcher::from_config(&config)?;
+    let schedules = session_schedules(&args, &config, &selection)?;
+
+    paths::ensure_dirs()?;
+    paths::migrate_legacy_files()?;
+    if let Some(pid) = read_session_pid() {
+        if pid != std::process::id() && is_session_running(pid) {
+            return Err(format!("A flow mode session is already running (PID {}). Use 'flowmode stop' to end it first.", pid).into());
+        }
+    }
+    if has_session_leftovers() {
+        println!("⚠️  The previous session did not shut down cleanly, recovering first...");
+        recover_session().await?;
+    }
+    if !selection.categories.is_empty() {
+        println!("🗂️  Blocking categories: {}", selection.categories.join(", "));
+    }
+    let base_plan = session_base_plan(&args, &config, &selection, &schedules).await;
+    let active_schedules = schedules.active(Local::now().naive_local());
+    if !active_schedules.is_empty() {
+        println!("🕘 Blocking on schedule: {}", active_schedules.join(", "));
+    }
+    let plan = schedules.plan(&base_plan, &active_schedules);
+    let mut blockers = build_blockers(&config, args.whitelist)?;
+    // Installed before anything is blocked so an early Ctrl+C still goes through teardown
+    let signals = SignalListener::install()
+        .map_err(|e| format!("Failed to install signal handlers: {}", e))?;
+    
+    println!("📵 Blocking distracting websites...");
+    let hosts_path = get_hosts_path();
+    apply_blockers(&mut blockers, &plan)?;
+    flush_dns_cache(&config);
+    for blocker in &blockers {
+        if let Err(e) = blocker.verify(&plan) {
+            eprintln!("Warning: Blocking with {} is not fully in place: {}", blocker.describe(), e);
+        }
+    }
+    let control = match ControlServer::bind().await {
+        Ok(control) => control,
+        Err(e) => {
+            revert_blockers(&mut blockers);
+            return Err(e);
+        }
+    };
+    
+    let block_page = match config.block_page.as_ref().filter(|settings| settings.enabled.unwrap_or(false)) {
+        Some(settings) => match BlockPageServer::start(settings, &plan.redirect_addresses).await {
+            Ok(server) => {
+                let addresses: Vec<String> = server.addresses().iter().map(|address| address.to_string()).collect();
+                println!("🪧 Serving the block page on {}", addresses.join(", "));
+                Some(server)
+            }
+            Err(e) => {
+                eprintln!("Warning: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    // Show guidance if using user-level hosts
+    if !is_system_hosts(&hosts_path) && !args.whitelist && blocker::uses_backend(&config, BlockingBackend::Hosts) {
+        print_user_hosts_guidance(&hosts_path);
+    }
+    // Only the hosts file and the DNS resolver change what names resolve to
+    if !args.whitelist && (blocker::uses_backend(&config, BlockingBackend::Hosts) || blocker::uses_backend(&config, BlockingBackend::Dns)) {
+        report_resolution(&plan).await;
+    }
+    
+    println!("🔪 Closing distracting applications...");
+    let app_enforcements = close_applications(&mut apps);
+    let processes_with = |outcome: AppOutcome| -> Vec<BlockedProcess> {
+        app_enforcements.iter().filter(|enforcement| enforcement.outcome == outcome).map(AppEnforcement::process).collect()
+    };
+    let (apps_killed, apps_frozen) = (processes_with(AppOutcome::Killed), processes_with(AppOutcome::Frozen));
+
+    let pid = std::process::id();
+    let task_name = args.task.as_deref().unwrap_or("No task specified");
+    let start_time = Local::now();
+    let state = SessionState {
+        pid,
+        task: task_name.to_string(),
+        started_at: start_time,
+        phase: Phase::Focus,
+        cycle: 1,
+        total_cycles: 1,
+        phase_deadline: start_time,
+        hosts_path,
+        apps_killed,
+        apps_frozen,
+        paused: None,
+        blockers: blockers.iter().map(|blocker| blocker.describe()).collect(),
+        categories: selection.categories.clone(),
+    };
+    // Websites are blocked and apps frozen by now, so a failure from here on
+    // has to undo them before it is reported
+    if let Err(e) = begin_session(&args, &state).await {
+        abort_start(&mut blockers, &state.apps_frozen, &config).await;
+        control.close().await;
+        return Err(e);
+    }
+
+    // The events only add detail to the log, so failing to write them is no reason to stop
+    let mut events = Vec::new();
+    if !selection.categories.is_empty() {
+        events.push(("categories", selection.categories.join(" ")));
+    }
+    if !selection.allow.is_empty() {
+        events.push(("allow", selection.allow.join(" ")));
+    }
+    events.extend(active_schedules.iter().map(|name| ("schedule", format!("{} blocked", name))));
+    events.extend(app_enforcements.iter().map(|enforcement| ("app", enforcement.to_string())));
+    for (event, detail) in events {
+        if let Err(e) = record_event(&start_time, event, &detail) {
+            eprintln!("Warning: Failed to record {} event: {}", event, e);
+        }
+    }
+    let watched_paths: Vec<PathBuf> = blockers.iter().flat_map(|blocker| blocker.watched_paths()).collect();
+    let tampered = vec![false; blockers.len()];
+    let mut session = Session {
+        config: config.clone(),
+        control,
+        signals,
+        blockers,
+        plan,
+        base_plan,
+        schedules,
+        active_schedules,
+        tamper: TamperWatcher::start(&watched_paths),
+        tampered,
+        apps,
+        _block_page: block_page,
+        interrupted_at: None,
+        state,
+        cycle_plan: None,
+        pause: None,
+        paused_total: Duration::ZERO,
+        stop_reply: None,
+    };
+
     if args.pomodoro.is_some() || config.pomodoro_defaults.is_some() {
-        // If duration is specified, calculate how many complete sessions can fit within that duration
-        let session_duration = humantime::parse_duration(&args.duration)
-            .map_err(|e| format!("Invalid duration '{}': {}. Use format like '25m', '1h', '30s', etc.", args.duration, e))?;
-        
-        let single_cycle_duration = pomodoro_duration + break_duration;
-        let max_sessions = (session_duration.as_secs() / single_cycle_duration.as_secs()).max(1) as u32;
-        let actual_cycles = cycles.min(max_sessions);
-        
-        for i in 1..=actual_cycles {
-            println!("🍅 Starting Pomodoro Work Session {}/{}", i, actual_cycles);
-            tokio::select! {
-                _ = tokio::time::sleep(pomodoro_duration) => {},
-                _ = rx.recv() => { println!("Pomodoro interrupted."); break; }
+        // Fit as many complete cycles as the session duration allows
+        session.cycle_plan = Some(CyclePlan {
+            budget: session_duration,
+            cycle_duration: pomodoro_duration + break_duration,
+            cycles,
+        });
+        session.update_total_cycles();
+
+        // The number of cycles can change while running when the session budget is adjusted
+        let mut i = 0;
+        while i < session.state.total_cycles {
+            i += 1;
+            println!("🍅 Starting Pomodoro Work Session {}/{}", i, session.state.total_cycles);
+            if session.run_phase(Phase::Work, i, pomodoro_duration).await == PhaseOutcome::Stopped {
+                println!("Pomodoro interrupted.");
+                break;
             }
             println!("✅ Work session {} completed!", i);
 
-            if i == actual_cycles {
+            if i >= session.state.total_cycles {
                 // Only do long break if we completed all originally planned cycles, not just duration-limited cycles
-                if actual_cycles == cycles {
+                if session.state.total_cycles == cycles {
                     println!("☕ Starting Long Break ({} minutes)", long_break_duration.as_secs() / 60);
-                    tokio::select! {
-                        _ = tokio::time::sleep(long_break_duration) => {},
-                        _ = rx.recv() => { println!("Pomodoro interrupted."); break; }
+                    if session.run_phase(Phase::LongBreak, i, long_break_duration).await == PhaseOutcome::Stopped {
+                        println!("Pomodoro interrupted.");
+                        break;
                     }
                     println!("✅ Long Break finished! Great work completing all cycles!");
                 } else {
@@ -706,54 +1412,271 @@ async fn start_flow_mode(args: StartArgs) -> Result<(), Box<dyn std::error::Erro
                 break;
             } else {
                 println!("☕ Starting Short Break ({} minutes)", break_duration.as_secs() / 60);
-                tokio::select! {
-                    _ = tokio::time::sleep(break_duration) => {},
-                    _ = rx.recv() => { println!("Pomodoro interrupted."); break; }
+                if session.run_phase(Phase::ShortBreak, i, break_duration).await == PhaseOutcome::Stopped {
+                    println!("Pomodoro interrupted.");
+                    break;
                 }
                 println!("✅ Short Break finished! Back to work.");
             }
         }
     } else {
         // If no pomodoro args, just sleep for the main duration
-        let duration = humantime::parse_duration(&args.duration).map_err(|e| format!("Invalid duration '{}': {}. Use format like '25m', '1h', '30s', etc.", args.duration, e))?;
-        tokio::select! {
-            _ = tokio::time::sleep(duration) => {},
-            _ = rx.recv() => { println!("Flow mode interrupted."); }
+        if session.run_phase(Phase::Focus, 1, session_duration).await == PhaseOutcome::Stopped {
+            println!("Flow mode interrupted.");
         }
     }
 
-    stop_flow_mode(StopArgs {}).await?;
+    session.finish().await?;
 
     Ok(())
 }
 
-async fn stop_flow_mode(_args: StopArgs) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(tx) = STOP_SIGNAL_SENDER.get() {
-        let _ = tx.send(()); // Send stop signal
+// The steps of starting a session that come after blocking: saving its
+// state, muting, the PID file and the start record in the log.
+async fn begin_session(args: &StartArgs, state: &SessionState) -> Result<(), Box<dyn std::error::Error>> {
+    // Saved first: should the process die below, recovery finds the frozen apps
+    state.save()?;
+    fs::write(paths::pid_file(), state.pid.to_string())?;
+
+    println!("🔇 Muting notifications...");
+    mute_notifications().await?;
+
+    if let Some(url) = &args.slack_webhook_url {
+        if let Err(e) = post_to_slack(url, "In flow mode, will reply later.").await {
+            eprintln!("Warning: Failed to post to Slack: {}", e);
+        }
     }
-    unblock_websites().await?;
-    unblock_applications().await?;
-    unmute_notifications().await?;
-    if fs::metadata("flowmode.pid").is_ok() {
-        fs::remove_file("flowmode.pid")?;
+
+    // Always log session start, with task name or "No task specified"
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(paths::log_file())?;
+    write!(file, "{},{},", state.task, state.started_at.to_rfc3339())?;
+
+    println!("✅ Flow mode activated! Focus time begins now.");
+    if let Some(ref task) = args.task {
+        println!("📝 Working on: {}", task);
+    }
+    println!("Logging task: {}", state.task);
+    Ok(())
+}
+
+// Undoes a session start that failed after blocking: lifts the blocks, lets
+// the frozen apps continue, unmutes and removes the state and PID files.
+async fn abort_start(blockers: &mut [Box<dyn Blocker>], apps_frozen: &[BlockedProcess], config: &Config) {
+    for failure in revert_blockers(blockers) {
+        eprintln!("Warning: {}", failure);
+    }
+    flush_dns_cache(config);
+    if let Err(e) = unblock_applications(apps_frozen).await {
+        eprintln!("Warning: Failed to resume frozen applications: {}", e);
+    }
+    if let Err(e) = unmute_notifications().await {
+        eprintln!("Warning: Failed to unmute notifications: {}", e);
+    }
+    SessionState::remove();
+    let _ = fs::remove_file(paths::pid_file());
+}
+
+// Teardown run by the session itself; it is the only writer of the end record,
+// which also carries the number of seconds the session spent paused.
+async fn end_session(blockers: &mut [Box<dyn Blocker>], apps_frozen: &[BlockedProcess], config: &Config, paused_total: Duration) -> Result<(), Box<dyn std::error::Error>> {
+    // A block that cannot be lifted must not keep frozen apps stopped or the
+    // sound muted, so everything else is restored before it is reported
+    let mut failures = revert_blockers(blockers);
+    flush_dns_cache(config);
+    if let Err(e) = unblock_applications(apps_frozen).await {
+        failures.push(format!("Failed to resume frozen applications: {}", e));
+    }
+    if let Err(e) = unmute_notifications().await {
+        failures.push(format!("Failed to unmute notifications: {}", e));
+    }
+    SessionState::remove();
+    let pid_file = paths::pid_file();
+    if pid_file.exists() {
+        fs::remove_file(&pid_file)?;
     }
 
     let mut file = fs::OpenOptions::new()
         .create(true)
         .append(true)
-        .open("log.csv")?;
+        .open(paths::log_file())?;
     let end_time = Local::now();
-    writeln!(file, "{}", end_time.to_rfc3339())?;
+    writeln!(file, "{},{}", end_time.to_rfc3339(), paused_total.as_secs())?;
 
+    if !failures.is_empty() {
+        return Err(failures.join("; ").into());
+    }
     println!("🎉 Flow mode session completed and logged successfully!");
 
     Ok(())
 }
 
+async fn stop_flow_mode(_args: StopArgs) -> Result<(), Box<dyn std::error::Error>> {
+    match send_request(&ControlRequest::Stop).await? {
+        Some(response) if response.ok => {
+            println!("🛑 {}", response.message);
+        }
+        Some(response) => {
+            return Err(response.message.into());
+        }
+        None => match read_session_pid() {
+            Some(pid) if is_session_running(pid) => {
+                return Err(format!("Flow mode session (PID {}) is not responding on its control channel", pid).into());
+            }
+            _ if has_session_leftovers() => {
+                // The session died without cleaning up after itself
+                println!("The last session is no longer running, restoring system state...");
+                recover_session().await?;
+            }
+            _ => println!("No flow mode session is running."),
+        },
+    }
+
+    Ok(())
+}
+
+// Sends a request to the running session and returns its confirmation message.
+async fn send_to_session(request: &ControlRequest) -> Result<String, Box<dyn std::error::Error>> {
+    match send_request(request).await? {
+        Some(response) if response.ok => Ok(response.message),
+        Some(response) => Err(response.message.into()),
+        None => Err("No flow mode session is running.".into()),
+    }
+}
+
+async fn pause_flow_mode(args: PauseArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let message = send_to_session(&ControlRequest::Pause { lift_blocks: args.lift_blocks }).await?;
+    println!("⏸️  {}", message);
+    Ok(())
+}
+
+async fn resume_flow_mode() -> Result<(), Box<dyn std::error::Error>> {
+    let message = send_to_session(&ControlRequest::Resume).await?;
+    println!("▶️  {}", message);
+    Ok(())
+}
+
+async fn adjust_flow_mode(args: AdjustArgs, extend: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let delta = humantime::parse_duration(&args.duration)
+        .map_err(|e| format!("Invalid duration '{}': {}. Use format like '10m', '1h', etc.", args.duration, e))?;
+    let seconds = i64::try_from(delta.as_secs()).map_err(|_| format!("Duration '{}' is too large", args.duration))?;
+    let seconds = if extend { seconds } else { -seconds };
+
+    let message = send_to_session(&ControlRequest::Adjust { seconds, whole_session: args.session }).await?;
+    println!("⏱️  {}", message);
+    Ok(())
+}
+
+async fn recover_flow_mode() -> Result<(), Box<dyn std::error::Error>> {
+    paths::migrate_legacy_files()?;
+    if let Some(pid) = read_session_pid().filter(|pid| is_session_running(*pid)) {
+        return Err(format!("A flow mode session is still running (PID {}). Use 'flowmode stop' instead.", pid).into());
+    }
+    if !has_session_leftovers() {
+        println!("Nothing to recover: no leftovers from an earlier session were found.");
+        return Ok(());
+    }
+    recover_session().await
+}
+
+async fn status_flow_mode(args: StatusArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let state = SessionState::load();
+    let pid = read_session_pid().or(state.as_ref().map(|s| s.pid));
+    let running = pid.is_some_and(is_session_running);
+    let stale = !running && (state.is_some() || pid.is_some());
+
+    if args.json {
+        let report = serde_json::json!({
+            "running": running,
+            "stale": stale,
+            "pid": pid,
+            "remaining_seconds": state.as_ref().filter(|_| running).map(|s| s.remaining().num_seconds()),
+            "session": state,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    let state = match state {
+        Some(state) if running => state,
+        _ if stale => {
+            println!("⚠️  Found state from a session that is no longer running (PID {}).", pid.map_or("unknown".to_string(), |p| p.to_string()));
+            println!("   Run 'flowmode recover' to restore your hosts file and audio.");
+            return Ok(());
+        }
+        _ => {
+            println!("No flow mode session is running.");
+            return Ok(());
+        }
+    };
+
+    println!("📝 Task: {}", state.task);
+    println!("⏱️  Phase: {} (cycle {}/{})", state.phase, state.cycle, state.total_cycles);
+    println!("   Remaining: {}", format_remaining(state.remaining()));
+    if let Some(pause) = &state.paused {
+        let lifted = if pause.blocks_lifted { ", blocks lifted" } else { "" };
+        println!("⏸️  Paused since {}{}", pause.since.format("%H:%M:%S"), lifted);
+    }
+    println!("   Started: {}", state.started_at.format("%Y-%m-%d %H:%M:%S"));
+    if state.blockers.is_empty() {
+        println!("📵 Hosts file: {}", state.hosts_path.display());
+    } else {
+        println!("📵 Blocking with: {}", state.blockers.join(", "));
+    }
+    if !state.categories.is_empty() {
+        println!("🗂️  Categories: {}", state.categories.join(", "));
+    }
+    if state.apps_killed.is_empty() {
+        println!("🔪 Apps closed: none");
+    } else {
+        let apps: Vec<String> = state.apps_killed.iter().map(|app| format!("{} (PID {})", app.name, app.pid)).collect();
+        println!("🔪 Apps closed: {}", apps.join(", "));
+    }
+    if !state.apps_frozen.is_empty() {
+        let apps: Vec<String> = state.apps_frozen.iter().map(|app| format!("{} (PID {})", app.name, app.pid)).collect();
+        println!("🧊 Apps frozen: {}", apps.join(", "));
+    }
+
+    Ok(())
+}
+
+async fn update_block_lists() -> Result<(), Box<dyn std::error::Error>> {
+    let sources = load_config().block_list_sources.unwrap_or_default();
+    if sources.is_empty() {
+        println!("No block lists configured. Add URLs or files to block_list_sources in config.toml.");
+        return Ok(());
+    }
+
+    let lists = load_block_lists(&sources, &paths::config_base_dir()).await;
+    let domains: std::collections::HashSet<&String> = lists.iter().flat_map(|list| &list.domains).collect();
+    let updated = lists.iter().filter(|list| !matches!(list.status, ListStatus::Cached { .. })).count();
+    println!("📋 {} domains from {} block lists", domains.len(), lists.len());
+    if updated < sources.len() {
+        return Err(format!("{} of {} block lists could not be updated", sources.len() - updated, sources.len()).into());
+    }
+    Ok(())
+}
+
+// The categories each session blocked, keyed by its start time as written in log.csv.
+fn session_categories() -> std::collections::HashMap<String, String> {
+    fs::read_to_string(paths::events_file())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, ',');
+            let (session_start, _, event, detail) = (fields.next()?, fields.next()?, fields.next()?, fields.next()?);
+            (event == "categories").then(|| (session_start.to_string(), detail.to_string()))
+        })
+        .collect()
+}
+
 async fn report_flow_sessions() -> Result<(), Box<dyn std::error::Error>> {
     println!("\n--- Flow Mode Session Report ---");
-    
-    let content = match fs::read_to_string("log.csv") {
+    paths::migrate_legacy_files()?;
+
+    let content = match fs::read_to_string(paths::log_file()) {
         Ok(content) => content,
         Err(e) => {
             eprintln!("Error reading log file: {}. Make sure you have completed at least one session.", e);
@@ -761,6 +1684,7 @@ async fn report_flow_sessions() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
+    let categories = session_categories();
     for (line_num, line) in content.lines().enumerate() {
         let line = line.trim();
         if line.is_empty() {
@@ -768,17 +1692,29 @@ async fn report_flow_sessions() -> Result<(), Box<dyn std::error::Error>> {
         }
         
         let parts: Vec<&str> = line.split(',').collect();
-        if parts.len() == 3 {
-            match (DateTime::parse_from_rfc3339(parts[1]), DateTime::parse_from_rfc3339(parts[2])) {
-                (Ok(start_time), Ok(end_time)) => {
+        if (3..=5).contains(&parts.len()) {
+            // Older records have no paused column; only aborted sessions have a fifth one
+            let paused_secs = parts.get(3).map_or(Ok(0), |p| p.parse::<i64>());
+            match (DateTime::parse_from_rfc3339(parts[1]), DateTime::parse_from_rfc3339(parts[2]), paused_secs) {
+                (Ok(start_time), Ok(end_time), Ok(paused_secs)) => {
                     let start_local = start_time.with_timezone(&Local);
                     let end_local = end_time.with_timezone(&Local);
-                    let duration = end_local.signed_duration_since(start_local);
+                    let paused = chrono::Duration::seconds(paused_secs);
+                    let duration = end_local.signed_duration_since(start_local) - paused;
 
                     println!("Task: {}", parts[0]);
                     println!("  Start: {}", start_local.format("%Y-%m-%d %H:%M:%S"));
                     println!("  End:   {}", end_local.format("%Y-%m-%d %H:%M:%S"));
                     println!("  Duration: {} minutes", duration.num_minutes());
+                    if paused_secs > 0 {
+                        println!("  Paused: {} minutes", paused.num_minutes());
+                    }
+                    if let Some(categories) = categories.get(parts[1]) {
+                        println!("  Categories: {}", categories.replace(' ', ", "));
+                    }
+                    if parts.get(4) == Some(&"aborted") {
+                        println!("  Status: aborted (session ended abnormally)");
+                    }
                     println!("--------------------------------");
                 }
                 _ => {
@@ -797,7 +1733,16 @@ pub async fn run(command: CliCommand) -> Result<(), Box<dyn std::error::Error>>
     match command {
         CliCommand::Start(args) => start_flow_mode(args).await?,
         CliCommand::Stop(args) => stop_flow_mode(args).await?,
+        CliCommand::Status(args) => status_flow_mode(args).await?,
+        CliCommand::Pause(args) => pause_flow_mode(args).await?,
+        CliCommand::Resume => resume_flow_mode().await?,
+        CliCommand::Extend(args) => adjust_flow_mode(args, true).await?,
+        CliCommand::Shorten(args) => adjust_flow_mode(args, false).await?,
+        CliCommand::Recover => recover_flow_mode().await?,
         CliCommand::Report => report_flow_sessions().await?,
+        CliCommand::Lists(args) => match args.command {
+            ListsCommand::Update => update_block_lists().await?,
+        },
     }
 
     Ok(())
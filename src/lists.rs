@@ -0,0 +1,210 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::paths;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Names that hosts-format lists map to local addresses for the system's own
+// use; blocking them would break the machine rather than a website
+const RESERVED_HOSTS: &[&str] = &[
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+    "ip6-loopback",
+    "ip6-localnet",
+    "ip6-mcastprefix",
+    "ip6-allnodes",
+    "ip6-allrouters",
+    "ip6-allhosts",
+];
+
+/// Extracts the domains from a block list, either in hosts format
+/// (`0.0.0.0 example.com`, several hostnames per line allowed) or with one
+/// plain domain per line. Comments, local names such as `localhost` and
+/// anything that is not a valid hostname are skipped, and duplicates are
+/// dropped. Domains are returned lowercased, in the order they first appear.
+pub fn parse_block_list(content: &str) -> Vec<String> {
+    let mut domains = Vec::new();
+    let mut seen = HashSet::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        // Adblock-style headers such as "[Adblock Plus 2.0]" or "! Title: ..."
+        if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let hosts = match fields.split_first() {
+            Some((first, rest)) if first.parse::<IpAddr>().is_ok() => rest,
+            _ if fields.len() == 1 => &fields[..],
+            _ => continue,
+        };
+        for host in hosts {
+            let host = host.trim_end_matches('.').to_ascii_lowercase();
+            if is_valid_domain(&host) && seen.insert(host.clone()) {
+                domains.push(host);
+            }
+        }
+    }
+    domains
+}
+
+fn is_valid_domain(host: &str) -> bool {
+    host.contains('.')
+        && !RESERVED_HOSTS.contains(&host)
+        && host.parse::<IpAddr>().is_err()
+        && host.split('.').all(|label| {
+            !label.is_empty() && !label.starts_with('-') && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+/// How the domains of a source were obtained.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListStatus {
+    /// A local file, read as it is now.
+    Local,
+    /// Downloaded because it was new or had changed.
+    Downloaded,
+    /// The server reported that the cached copy is still current.
+    Unchanged,
+    /// The download failed, so the cached copy from `fetched_at` was used.
+    Cached { fetched_at: DateTime<Local>, error: String },
+}
+
+/// One block list source after loading.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedList {
+    pub source: String,
+    pub domains: Vec<String>,
+    pub status: ListStatus,
+}
+
+impl std::fmt::Display for LoadedList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match &self.status {
+            ListStatus::Local => "local file".to_string(),
+            ListStatus::Downloaded => "downloaded".to_string(),
+            ListStatus::Unchanged => "unchanged".to_string(),
+            ListStatus::Cached { fetched_at, error } => {
+                format!("offline, using the copy from {} ({})", fetched_at.format("%Y-%m-%d %H:%M"), error)
+            }
+        };
+        write!(f, "{}: {} domains, {}", self.source, self.domains.len(), status)
+    }
+}
+
+// What is remembered about a downloaded source next to its cached content
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    source: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    fetched_at: DateTime<Local>,
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+// Content and metadata files for a URL, named after a stable hash of it
+fn cache_files(url: &str) -> (PathBuf, PathBuf) {
+    // FNV-1a; std's hasher is not guaranteed to stay the same between releases
+    let hash = url.bytes().fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    let dir = paths::cache_dir().join("lists");
+    (dir.join(format!("{:016x}.txt", hash)), dir.join(format!("{:016x}.json", hash)))
+}
+
+/// Loads one source. Local files are read directly, with relative paths taken
+/// from `base_dir`. URLs are fetched with the ETag and Last-Modified of the
+/// cached copy, so an unchanged list is not downloaded again; if the server
+/// cannot be reached the cached copy is used. Fails only when there is neither
+/// a download nor a cached copy.
+pub async fn load_list(client: &reqwest::Client, source: &str, base_dir: &Path) -> Result<LoadedList, Box<dyn std::error::Error>> {
+    let source = source.trim();
+    if !is_url(source) {
+        let path = base_dir.join(source);
+        let content = fs::read_to_string(&path).map_err(|e| format!("Cannot read block list {}: {}", path.display(), e))?;
+        return Ok(LoadedList { source: source.to_string(), domains: parse_block_list(&content), status: ListStatus::Local });
+    }
+
+    let (content_file, entry_file) = cache_files(source);
+    let cached_entry: Option<CacheEntry> = fs::read_to_string(&entry_file)
+        .ok()
+        .and_then(|entry| serde_json::from_str(&entry).ok())
+        .filter(|_| content_file.exists());
+
+    let (content, status) = match fetch(client, source, cached_entry.as_ref()).await {
+        Ok(Some((content, etag, last_modified))) => {
+            fs::create_dir_all(paths::cache_dir().join("lists"))?;
+            fs::write(&content_file, &content)?;
+            let entry = CacheEntry { source: source.to_string(), etag, last_modified, fetched_at: Local::now() };
+            fs::write(&entry_file, serde_json::to_string_pretty(&entry)?)?;
+            (content, ListStatus::Downloaded)
+        }
+        Ok(None) => (fs::read_to_string(&content_file)?, ListStatus::Unchanged),
+        Err(e) => match &cached_entry {
+            Some(entry) => {
+                let status = ListStatus::Cached { fetched_at: entry.fetched_at, error: e.to_string() };
+                (fs::read_to_string(&content_file)?, status)
+            }
+            None => return Err(format!("Cannot download block list {}: {}", source, e).into()),
+        },
+    };
+    Ok(LoadedList { source: source.to_string(), domains: parse_block_list(&content), status })
+}
+
+// Returns the new content with its ETag and Last-Modified headers, or None if
+// the cached copy is still current.
+async fn fetch(client: &reqwest::Client, url: &str, cached: Option<&CacheEntry>) -> Result<Option<(String, Option<String>, Option<String>)>, Box<dyn std::error::Error>> {
+    let mut request = client.get(url).timeout(FETCH_TIMEOUT);
+    if let Some(cached) = cached {
+        if let Some(etag) = &cached.etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let response = request.send().await?;
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED && cached.is_some() {
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        return Err(format!("server answered {}", response.status()).into());
+    }
+    let header = |name: reqwest::header::HeaderName| response.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+    let etag = header(reqwest::header::ETAG);
+    let last_modified = header(reqwest::header::LAST_MODIFIED);
+    Ok(Some((response.text().await?, etag, last_modified)))
+}
+
+/// Loads every source in turn, local files relative to `base_dir`, and prints
+/// how each went. Sources that fail are reported and skipped, so a session
+/// still starts without them.
+pub async fn load_block_lists(sources: &[String], base_dir: &Path) -> Vec<LoadedList> {
+    let client = reqwest::Client::new();
+    let mut lists = Vec::new();
+    for source in sources {
+        match load_list(&client, source, base_dir).await {
+            Ok(list) => {
+                if matches!(list.status, ListStatus::Cached { .. }) {
+                    eprintln!("Warning: Block list {}", list);
+                } else {
+                    println!("📋 Block list {}", list);
+                }
+                lists.push(list);
+            }
+            Err(e) => eprintln!("Warning: {}", e),
+        }
+    }
+    lists
+}
@@ -0,0 +1,169 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Overrides both the state and the data directory when set.
+pub const HOME_ENV: &str = "FLOWMODE_HOME";
+
+const APP_DIR: &str = "flowmode";
+
+/// Runtime files that only matter while a session is running or being
+/// recovered: the PID file, state snapshot, control endpoint and legacy backups.
+/// `$XDG_STATE_HOME/flowmode`, falling back to `~/.local/state/flowmode`.
+pub fn state_dir() -> PathBuf {
+    base_dir("XDG_STATE_HOME", &[".local", "state"])
+}
+
+/// Files worth keeping across sessions: the session log and the event log.
+/// `$XDG_DATA_HOME/flowmode`, falling back to `~/.local/share/flowmode`.
+pub fn data_dir() -> PathBuf {
+    base_dir("XDG_DATA_HOME", &[".local", "share"])
+}
+
+/// Downloaded copies of block list sources, which can be fetched again at any time.
+/// `$XDG_CACHE_HOME/flowmode`, falling back to `~/.cache/flowmode`.
+pub fn cache_dir() -> PathBuf {
+    base_dir("XDG_CACHE_HOME", &[".cache"])
+}
+
+/// The directory relative paths in the configuration, such as local block
+/// lists, are resolved against: the one holding the `config.toml` in use,
+/// which is read from the current directory.
+pub fn config_base_dir() -> PathBuf {
+    std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
+}
+
+fn base_dir(xdg_var: &str, home_fallback: &[&str]) -> PathBuf {
+    if let Some(dir) = non_empty_var(HOME_ENV) {
+        return PathBuf::from(dir);
+    }
+    // The spec says relative values are invalid and must be ignored
+    if let Some(dir) = non_empty_var(xdg_var).map(PathBuf::from).filter(|dir| dir.is_absolute()) {
+        return dir.join(APP_DIR);
+    }
+    if cfg!(windows) {
+        if let Some(dir) = non_empty_var("LOCALAPPDATA") {
+            return PathBuf::from(dir).join(APP_DIR);
+        }
+    }
+    match non_empty_var("HOME").or_else(|| non_empty_var("USERPROFILE")) {
+        Some(home) => home_fallback.iter().fold(PathBuf::from(home), |path, part| path.join(part)).join(APP_DIR),
+        None => PathBuf::from("."),
+    }
+}
+
+fn non_empty_var(name: &str) -> Option<std::ffi::OsString> {
+    std::env::var_os(name).filter(|value| !value.is_empty())
+}
+
+pub fn pid_file() -> PathBuf {
+    state_dir().join("flowmode.pid")
+}
+
+pub fn state_file() -> PathBuf {
+    state_dir().join("flowmode.state.json")
+}
+
+pub fn log_file() -> PathBuf {
+    data_dir().join("log.csv")
+}
+
+pub fn events_file() -> PathBuf {
+    data_dir().join("events.csv")
+}
+
+/// Creates the state and data directories if they do not exist yet.
+pub fn ensure_dirs() -> Result<(), Box<dyn std::error::Error>> {
+    for dir in [state_dir(), data_dir()] {
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Moves files that older versions wrote to the current directory into the
+/// state and data directories. Logs are appended to the existing ones so
+/// sessions recorded from different folders end up in a single log; any other
+/// file is only moved if nothing is there yet. A `log.csv` or `events.csv`
+/// whose rows are not flowmode records belongs to something else and is left
+/// where it is.
+pub fn migrate_legacy_files() -> Result<(), Box<dyn std::error::Error>> {
+    let Ok(cwd) = std::env::current_dir() else {
+        return Ok(());
+    };
+    let legacy_files = [
+        ("log.csv", log_file(), true),
+        ("events.csv", events_file(), true),
+        ("flowmode.pid", pid_file(), false),
+        ("flowmode.state.json", state_file(), false),
+        ("hosts.backup", state_dir().join("hosts.backup"), false),
+        ("user_hosts.backup", state_dir().join("user_hosts.backup"), false),
+    ];
+
+    for (name, target, append) in legacy_files {
+        let legacy = cwd.join(name);
+        if !legacy.is_file() || is_same_directory(&cwd, &target) {
+            continue;
+        }
+        ensure_dirs()?;
+
+        if append && !is_flowmode_log(&fs::read_to_string(&legacy).unwrap_or_default(), name) {
+            println!(
+                "ℹ️  Leaving {} in place: it does not look like a flowmode log. If it is one, append it to {} by hand.",
+                legacy.display(),
+                target.display()
+            );
+            continue;
+        }
+        if append && target.exists() {
+            let existing = fs::read_to_string(&target)?;
+            if !existing.is_empty() && !existing.ends_with('\n') {
+                // The target log has a session that is still open; merging now would split its row
+                eprintln!("Warning: Not migrating {} while {} has an unfinished session", legacy.display(), target.display());
+                continue;
+            }
+            let content = fs::read_to_string(&legacy)?;
+            let mut file = fs::OpenOptions::new().append(true).open(&target)?;
+            file.write_all(content.as_bytes())?;
+            fs::remove_file(&legacy)?;
+        } else if target.exists() {
+            eprintln!("Warning: Not migrating {} because {} already exists", legacy.display(), target.display());
+            continue;
+        } else {
+            move_file(&legacy, &target)?;
+        }
+        println!("📦 Moved {} to {}", legacy.display(), target.display());
+    }
+    Ok(())
+}
+
+// Session rows are `task,start,end,...` (the task may hold commas, and the
+// last row may still be open); event rows are `session_start,timestamp,event,detail`.
+fn is_flowmode_log(content: &str, name: &str) -> bool {
+    let is_timestamp = |field: &str| chrono::DateTime::parse_from_rfc3339(field.trim()).is_ok();
+    let mut rows = content.lines().filter(|line| !line.trim().is_empty()).peekable();
+    rows.peek().is_some()
+        && rows.all(|row| {
+            let fields: Vec<&str> = row.split(',').collect();
+            match name {
+                "events.csv" => fields.len() >= 4 && is_timestamp(fields[0]) && is_timestamp(fields[1]),
+                _ => fields.iter().skip(1).any(|field| is_timestamp(field)),
+            }
+        })
+}
+
+fn is_same_directory(dir: &Path, file: &Path) -> bool {
+    match (dir.canonicalize(), file.parent().map(Path::canonicalize)) {
+        (Ok(dir), Some(Ok(parent))) => dir == parent,
+        _ => false,
+    }
+}
+
+// The working directory may be on a different filesystem than the state directory
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
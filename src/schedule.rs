@@ -0,0 +1,173 @@
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Weekday};
+
+use crate::{BlockPlan, Config, ScheduleWindow, Selection};
+
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// When a category or domain from the `[schedules]` table is blocked.
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    name: String,
+    entries: Vec<String>,
+    windows: Vec<Window>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Window {
+    // Indexed from Monday
+    days: [bool; 7],
+    // None covers the whole day; an end before the start runs past midnight
+    hours: Option<(NaiveTime, NaiveTime)>,
+}
+
+impl Window {
+    fn contains(&self, now: NaiveDateTime) -> bool {
+        let today = now.weekday().num_days_from_monday() as usize;
+        let yesterday = (today + 6) % 7;
+        let time = now.time();
+        match self.hours {
+            None => self.days[today],
+            Some((start, end)) if start < end => self.days[today] && start <= time && time < end,
+            // Overnight windows belong to the day they start on
+            Some((start, end)) => (self.days[today] && time >= start) || (self.days[yesterday] && time < end),
+        }
+    }
+
+    fn boundaries(&self) -> Vec<NaiveTime> {
+        match self.hours {
+            Some((start, end)) => vec![start, end],
+            None => vec![NaiveTime::MIN],
+        }
+    }
+}
+
+/// Categories and domains that are only blocked at certain times, such as
+/// news during working hours. Everything else in the plan stays blocked for
+/// the whole session.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schedules {
+    rules: Vec<Rule>,
+}
+
+impl Schedules {
+    /// Reads the `[schedules]` table. Each key is a category from
+    /// `[categories]` or a domain. What the session asked for with `--block`,
+    /// categories and domains alike, is left out since it was asked for the
+    /// whole session, and domains in `--allow` are never blocked.
+    pub fn from_config(config: &Config, selection: &Selection) -> Result<Self, Box<dyn std::error::Error>> {
+        let categories = config.categories.clone().unwrap_or_default();
+        let hosts = |entries: &[String]| -> Vec<String> { entries.iter().map(|entry| entry_host(entry)).collect() };
+        let (blocked, allowed) = (hosts(&selection.block), hosts(&selection.allow));
+        let mut rules = Vec::new();
+        for (name, windows) in config.schedules.clone().unwrap_or_default() {
+            let key = name.trim().to_ascii_lowercase();
+            let entries = match categories.iter().find(|(category, _)| category.to_ascii_lowercase() == key) {
+                Some((category, _)) if selection.categories.contains(category) => continue,
+                Some((_, domains)) => domains.clone(),
+                None if key.contains('.') && blocked.contains(&entry_host(&key)) => continue,
+                None if key.contains('.') => vec![key.trim_end_matches('.').to_string()],
+                None => return Err(format!("Unknown category '{}' in the [schedules] section of config.toml", name).into()),
+            };
+            let entries = entries.into_iter().filter(|entry| !allowed.contains(&entry_host(entry))).collect();
+            let windows = windows
+                .iter()
+                .map(parse_window)
+                .collect::<Result<Vec<_>, String>>()
+                .map_err(|e| format!("Invalid schedule for '{}': {}", name, e))?;
+            rules.push(Rule { name, entries, windows });
+        }
+        Ok(Schedules { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The hostnames of every scheduled domain given directly, which must be
+    /// taken out of the always-blocked part of the plan.
+    pub fn scheduled_domains(&self) -> Vec<String> {
+        self.rules.iter().filter(|rule| rule.name.contains('.')).flat_map(|rule| rule.entries.iter().map(|entry| entry_host(entry))).collect()
+    }
+
+    /// Names of the schedules with a window open at `now`, in config order.
+    pub fn active(&self, now: NaiveDateTime) -> Vec<String> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.windows.iter().any(|window| window.contains(now)))
+            .map(|rule| rule.name.clone())
+            .collect()
+    }
+
+    /// `base` with the entries of the `active` schedules added.
+    pub fn plan(&self, base: &BlockPlan, active: &[String]) -> BlockPlan {
+        let mut plan = base.clone();
+        for rule in self.rules.iter().filter(|rule| active.contains(&rule.name)) {
+            plan.add_domains(rule.entries.iter().cloned());
+        }
+        plan
+    }
+
+    /// The next time after `now` at which a window may open or close.
+    pub fn next_change(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let windows = self.rules.iter().flat_map(|rule| &rule.windows);
+        windows
+            .flat_map(|window| window.boundaries())
+            .flat_map(|time| (0..=7).map(move |days| (now.date() + Duration::days(days)).and_time(time)))
+            .filter(|candidate| *candidate > now)
+            .min()
+    }
+}
+
+fn entry_host(entry: &str) -> String {
+    let host = entry.split_whitespace().last().unwrap_or_default();
+    host.strip_prefix("*.").unwrap_or(host).trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn parse_window(window: &ScheduleWindow) -> Result<Window, String> {
+    let days = match &window.days {
+        None => [true; 7],
+        Some(names) => {
+            let mut days = [false; 7];
+            for name in names {
+                for day in parse_days(name)? {
+                    days[day] = true;
+                }
+            }
+            days
+        }
+    };
+    let hours = match &window.hours {
+        None => None,
+        Some(hours) => {
+            let (start, end) = hours.split_once('-').ok_or_else(|| format!("hours '{}' must look like 09:00-17:00", hours))?;
+            let parse = |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| format!("'{}' is not a time like 09:00", time.trim()));
+            let (start, end) = (parse(start)?, parse(end)?);
+            // 00:00-00:00 and the like cover the whole day
+            (start != end).then_some((start, end))
+        }
+    };
+    Ok(Window { days, hours })
+}
+
+// A day ("mon", "Monday"), a range ("mon-fri") or "weekdays" / "weekends",
+// as indexes from Monday
+fn parse_days(name: &str) -> Result<Vec<usize>, String> {
+    let name = name.trim().to_ascii_lowercase();
+    let day = |name: &str| -> Result<usize, String> {
+        name.parse::<Weekday>()
+            .map(|day| day.num_days_from_monday() as usize)
+            .map_err(|_| format!("unknown day '{}', use one of {}", name, WEEKDAYS.join(", ")))
+    };
+    match name.as_str() {
+        "weekdays" => Ok((0..5).collect()),
+        "weekends" => Ok(vec![5, 6]),
+        _ => match name.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (day(first.trim())?, day(last.trim())?);
+                // Ranges may wrap around the week, as in "fri-mon"
+                Ok((0..=(last + 7 - first) % 7).map(|offset| (first + offset) % 7).collect())
+            }
+            None => Ok(vec![day(&name)?]),
+        },
+    }
+}
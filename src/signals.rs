@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+/// What the session does when it receives Ctrl+C.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignalPolicy {
+    /// End the session and restore everything right away.
+    #[default]
+    Immediate,
+    /// Strict mode: Ctrl+C has to be pressed a second time to end the session.
+    Confirm,
+}
+
+/// A request from the OS to end the process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminationSignal {
+    /// Ctrl+C in the terminal (SIGINT).
+    Interrupt,
+    /// SIGTERM, or the console being closed or the system shutting down on Windows.
+    Terminate,
+    /// The controlling terminal went away (SIGHUP), or the user logged off on Windows.
+    Hangup,
+}
+
+impl std::fmt::Display for TerminationSignal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TerminationSignal::Interrupt => "SIGINT",
+            TerminationSignal::Terminate => "SIGTERM",
+            TerminationSignal::Hangup => "SIGHUP",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Catches termination signals for the lifetime of a session so they end it
+/// through the normal teardown instead of killing the process.
+pub struct SignalListener {
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    hangup: tokio::signal::unix::Signal,
+    #[cfg(windows)]
+    interrupt: tokio::signal::windows::CtrlC,
+    #[cfg(windows)]
+    close: tokio::signal::windows::CtrlClose,
+    #[cfg(windows)]
+    shutdown: tokio::signal::windows::CtrlShutdown,
+    #[cfg(windows)]
+    logoff: tokio::signal::windows::CtrlLogoff,
+}
+
+impl SignalListener {
+    #[cfg(unix)]
+    pub fn install() -> std::io::Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+        Ok(SignalListener {
+            interrupt: signal(SignalKind::interrupt())?,
+            terminate: signal(SignalKind::terminate())?,
+            hangup: signal(SignalKind::hangup())?,
+        })
+    }
+
+    #[cfg(windows)]
+    pub fn install() -> std::io::Result<Self> {
+        use tokio::signal::windows::{ctrl_c, ctrl_close, ctrl_logoff, ctrl_shutdown};
+        Ok(SignalListener {
+            interrupt: ctrl_c()?,
+            close: ctrl_close()?,
+            shutdown: ctrl_shutdown()?,
+            logoff: ctrl_logoff()?,
+        })
+    }
+
+    #[cfg(unix)]
+    pub async fn recv(&mut self) -> TerminationSignal {
+        tokio::select! {
+            _ = self.interrupt.recv() => TerminationSignal::Interrupt,
+            _ = self.terminate.recv() => TerminationSignal::Terminate,
+            _ = self.hangup.recv() => TerminationSignal::Hangup,
+        }
+    }
+
+    #[cfg(windows)]
+    pub async fn recv(&mut self) -> TerminationSignal {
+        tokio::select! {
+            _ = self.interrupt.recv() => TerminationSignal::Interrupt,
+            _ = self.close.recv() => TerminationSignal::Terminate,
+            _ = self.shutdown.recv() => TerminationSignal::Terminate,
+            _ = self.logoff.recv() => TerminationSignal::Hangup,
+        }
+    }
+}
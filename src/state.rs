@@ -0,0 +1,112 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::paths;
+
+/// Which part of the session is currently running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// A plain focus session without pomodoro cycles.
+    Focus,
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            Phase::Focus => "Focus",
+            Phase::Work => "Work",
+            Phase::ShortBreak => "Short break",
+            Phase::LongBreak => "Long break",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// A process that was closed when the session started.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockedProcess {
+    pub name: String,
+    pub pid: u32,
+}
+
+/// Set while the session is paused.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PauseState {
+    pub since: DateTime<Local>,
+    /// Time that was left in the phase when the pause began.
+    pub remaining_secs: u64,
+    /// Whether websites were unblocked and audio unmuted for the pause.
+    pub blocks_lifted: bool,
+}
+
+/// Snapshot of a running session, kept on disk so other `flowmode` invocations
+/// can report on it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionState {
+    pub pid: u32,
+    pub task: String,
+    pub started_at: DateTime<Local>,
+    pub phase: Phase,
+    pub cycle: u32,
+    pub total_cycles: u32,
+    pub phase_deadline: DateTime<Local>,
+    pub hosts_path: PathBuf,
+    pub apps_killed: Vec<BlockedProcess>,
+    /// Processes suspended under the `freeze` app block policy, continued when
+    /// the session ends.
+    #[serde(default)]
+    pub apps_frozen: Vec<BlockedProcess>,
+    #[serde(default)]
+    pub paused: Option<PauseState>,
+    /// Descriptions of the website blocking backends in use.
+    #[serde(default)]
+    pub blockers: Vec<String>,
+    /// Block list categories chosen with `--block`.
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+impl SessionState {
+    pub fn load() -> Option<SessionState> {
+        let content = fs::read_to_string(paths::state_file()).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Writes the state through a temporary file so readers never see a partial document.
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = paths::state_file();
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    pub fn remove() {
+        let _ = fs::remove_file(paths::state_file());
+    }
+
+    /// Time left in the current phase, never negative. Frozen while paused.
+    pub fn remaining(&self) -> chrono::Duration {
+        match &self.paused {
+            Some(pause) => chrono::Duration::seconds(pause.remaining_secs as i64),
+            None => (self.phase_deadline - Local::now()).max(chrono::Duration::zero()),
+        }
+    }
+}
+
+/// Formats a duration as `HH:MM:SS`, or `MM:SS` when under an hour.
+pub fn format_remaining(remaining: chrono::Duration) -> String {
+    let seconds = remaining.num_seconds().max(0);
+    let (hours, minutes, seconds) = (seconds / 3600, (seconds % 3600) / 60, seconds % 60);
+    if hours > 0 {
+        format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
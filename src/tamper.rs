@@ -0,0 +1,142 @@
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant, Interval, MissedTickBehavior};
+
+// Catches changes no file event reports, such as a stopped resolver or a
+// deleted firewall table
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+// Editors, and our own atomic writes, touch a file several times in a row
+const SETTLE_DELAY: Duration = Duration::from_millis(250);
+
+/// What the session does when it finds a block removed or edited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TamperPolicy {
+    /// Put the block back and record a `tamper` event.
+    #[default]
+    Reapply,
+    /// Only record a `tamper` event; the block stays as it was left.
+    Log,
+}
+
+/// Tells the session when to check that its blocks are still in place: right
+/// after one of the files the blockers wrote changes, and periodically.
+pub struct TamperWatcher {
+    // Dropping the watcher stops it; None when file events are unavailable
+    _watcher: Option<RecommendedWatcher>,
+    changes: mpsc::UnboundedReceiver<()>,
+    // Kept across calls, so other events waking the session loop do not
+    // push the periodic check back
+    interval: Interval,
+    // When the burst of writes after a change counts as settled; kept across
+    // calls, so a change is not lost when another event wakes the session
+    // loop while it settles
+    settle: Option<Instant>,
+}
+
+impl TamperWatcher {
+    /// Watches `paths`. If the OS refuses (e.g. out of inotify watches), only
+    /// the periodic check remains.
+    pub fn start(paths: &[PathBuf]) -> Self {
+        let (sender, changes) = mpsc::unbounded_channel();
+        let watcher = match watch(paths, sender) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                eprintln!("Warning: Cannot watch blocked files for changes, checking every {}s instead: {}", CHECK_INTERVAL.as_secs(), e);
+                None
+            }
+        };
+        let mut interval = tokio::time::interval_at(Instant::now() + CHECK_INTERVAL, CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        TamperWatcher { _watcher: watcher, changes, interval, settle: None }
+    }
+
+    /// Waits until a watched file changed and the burst of writes it belongs to
+    /// settled, or until the check interval passed.
+    /// Cancel safe: a change that came in before a cancelled call is checked
+    /// on the next one.
+    pub async fn next_check(&mut self) {
+        loop {
+            match self.settle {
+                Some(deadline) => {
+                    tokio::time::sleep_until(deadline).await;
+                    self.settle = None;
+                    while self.changes.try_recv().is_ok() {}
+                    // This check covers the next periodic one
+                    self.interval.reset();
+                    return;
+                }
+                None => tokio::select! {
+                    Some(()) = self.changes.recv() => self.settle = Some(Instant::now() + SETTLE_DELAY),
+                    _ = self.interval.tick() => return,
+                },
+            }
+        }
+    }
+}
+
+fn watch(paths: &[PathBuf], sender: mpsc::UnboundedSender<()>) -> notify::Result<RecommendedWatcher> {
+    // The directories are watched rather than the files, since replacing a file
+    // by renaming over it ends a watch on the file itself
+    let mut targets: Vec<PathBuf> = Vec::new();
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let Some(file_name) = path.file_name() else {
+            continue;
+        };
+        // Event paths are reported under the watched path, so compare against that
+        let dir = dir.canonicalize().unwrap_or(dir);
+        targets.push(dir.join(file_name));
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        // Reads, including the session's own checks, are not changes
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        if event.paths.iter().any(|path| targets.contains(path)) {
+            let _ = sender.send(());
+        }
+    })?;
+    for dir in &dirs {
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    }
+    Ok(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_change_survives_a_cancelled_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts");
+        std::fs::write(&path, "").unwrap();
+        let mut watcher = TamperWatcher::start(std::slice::from_ref(&path));
+
+        // Another event wakes the session loop while the change settles
+        std::fs::write(&path, "edited").unwrap();
+        tokio::select! {
+            _ = watcher.next_check() => panic!("checked before the change settled"),
+            _ = tokio::time::sleep(Duration::from_millis(150)) => {}
+        }
+
+        // The change is still checked, not left to the periodic check
+        tokio::time::timeout(Duration::from_secs(2), watcher.next_check())
+            .await
+            .expect("the change was lost");
+    }
+}
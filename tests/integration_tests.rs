@@ -1,12 +1,12 @@
-use flowmode::{run, CliCommand, StartArgs, StopArgs};
+use flowmode::{run, Blocker, CliCommand, StartArgs, StopArgs};
 use std::fs;
 use std::path::PathBuf;
 use std::time::Duration;
 use tempfile::NamedTempFile;
-use std::sync::Mutex;
+use tokio::sync::Mutex;
 
 // Mutex to prevent tests from running concurrently
-static TEST_MUTEX: Mutex<()> = Mutex::new(());
+static TEST_MUTEX: Mutex<()> = Mutex::const_new(());
 
 #[tokio::test]
 async fn test_config_loading() {
@@ -48,6 +48,7 @@ async fn test_hosts_path_unix() {
 
 #[tokio::test]
 async fn test_hosts_path_custom() {
+    let _guard = TEST_MUTEX.lock().await;
     std::env::set_var("FLOWMODE_TEST_HOSTS_FILE", "/tmp/test_hosts");
     let hosts_path = flowmode::get_hosts_path();
     assert_eq!(hosts_path, PathBuf::from("/tmp/test_hosts"));
@@ -55,136 +56,82 @@ async fn test_hosts_path_custom() {
 }
 
 #[tokio::test]
-async fn test_website_blocking_and_unblocking() {
-    let _guard = TEST_MUTEX.lock().unwrap();
-    
-    // Create a temporary hosts file for testing
+async fn test_blocking_matches_whole_hostnames() {
     let temp_file = NamedTempFile::new().unwrap();
-    let temp_path = temp_file.path().to_str().unwrap();
-    
-    // Store current env var if any
-    let old_env = std::env::var("FLOWMODE_TEST_HOSTS_FILE").ok();
-    
-    // Set environment variable to use our test file
-    std::env::set_var("FLOWMODE_TEST_HOSTS_FILE", temp_path);
-    
-    // Write initial content to test file
-    fs::write(temp_path, "127.0.0.1 localhost\n").unwrap();
-    
-    // Create test start args with explicit whitelist = false
+    let temp_path = temp_file.path();
+    fs::write(temp_path, "127.0.0.1 localhost\n127.0.0.1 box.com\n127.0.0.1 www.x.com # added by hand\n").unwrap();
+    let mut blockers: Vec<Box<dyn Blocker>> = vec![Box::new(flowmode::HostsBlocker::new(temp_path.to_path_buf()))];
+
     let start_args = StartArgs {
         duration: "1m".to_string(),
-        task: Some("Test task".to_string()),
+        task: None,
         slack_webhook_url: None,
-        whitelist: false,  // Explicitly false
+        whitelist: false,
         pomodoro: None,
         r#break: None,
         long_break: None,
         cycles: None,
+        block: vec![],
+        allow: vec![],
     };
-    
-    // Test config with ONLY our blocked site (no defaults)
     let config = flowmode::Config {
-        block_list: Some(vec!["127.0.0.1 example.com".to_string()]),
+        block_list: Some(vec!["x.com".to_string()]),
         app_block_list: None,
-        whitelist: None,
-        pomodoro_defaults: None,
+        ..flowmode::Config::default()
     };
-    
-    println!("Test config - whitelist mode: {}", start_args.whitelist);
-    println!("Test config - block_list: {:?}", config.block_list);
-    
-    // Test blocking
-    flowmode::block_websites(&start_args, &config).await.unwrap();
-    
-    let blocked_content = fs::read_to_string(temp_path).unwrap();
-    println!("Blocked content: {}", blocked_content);
-    // Debug information
-    println!("Expected to find: 127.0.0.1 example.com");
-    println!("Actual content length: {}", blocked_content.len());
-    println!("Original content length: {}", "127.0.0.1 localhost\n".len());
-    println!("Does content contain 'example.com'? {}", blocked_content.contains("example.com"));
-    
-    // The website should have been added to the hosts file
-    // Check for either the full line or just the domain
-    assert!(blocked_content.contains("example.com"), 
-            "Should contain example.com in the hosts file.\nActual content: '{}'", blocked_content);
-    
-    // Test unblocking
-    flowmode::unblock_websites().await.unwrap();
-    
-    let unblocked_content = fs::read_to_string(temp_path).unwrap();
-    assert_eq!(unblocked_content, "127.0.0.1 localhost\n");
-    
-    // Clean up - restore previous env var if it existed
-    if let Some(val) = old_env {
-        std::env::set_var("FLOWMODE_TEST_HOSTS_FILE", val);
-    } else {
-        std::env::remove_var("FLOWMODE_TEST_HOSTS_FILE");
-    }
+
+    // "box.com" must not hide "x.com", while the existing www entry is not duplicated
+    flowmode::block_websites(&mut blockers, &start_args, &config).await.unwrap();
+    let blocked = fs::read_to_string(temp_path).unwrap();
+    assert!(blocked.contains("# BEGIN flowmode\n127.0.0.1 x.com\n::1 x.com\n::1 www.x.com\n# END flowmode\n"), "Unexpected hosts file: {}", blocked);
+
+    flowmode::unblock_websites(&mut blockers, &config).await.unwrap();
+    assert_eq!(fs::read_to_string(temp_path).unwrap(), "127.0.0.1 localhost\n127.0.0.1 box.com\n127.0.0.1 www.x.com # added by hand\n");
 }
 
 #[tokio::test]
-async fn test_whitelist_mode() {
-    let _guard = TEST_MUTEX.lock().unwrap();
-    
-    // Create a temporary hosts file for testing
+async fn test_unblocking_keeps_edits_made_during_session() {
     let temp_file = NamedTempFile::new().unwrap();
-    let temp_path = temp_file.path().to_str().unwrap();
-    
-    // Store current env var if any
-    let old_env = std::env::var("FLOWMODE_TEST_HOSTS_FILE").ok();
-    
-    std::env::set_var("FLOWMODE_TEST_HOSTS_FILE", temp_path);
+    let temp_path = temp_file.path();
     fs::write(temp_path, "127.0.0.1 localhost\n").unwrap();
-    
+    let mut blockers: Vec<Box<dyn Blocker>> = vec![Box::new(flowmode::HostsBlocker::new(temp_path.to_path_buf()))];
+
     let start_args = StartArgs {
         duration: "1m".to_string(),
-        task: Some("Test task".to_string()),
+        task: None,
         slack_webhook_url: None,
-        whitelist: true,  // Explicitly true for whitelist mode
+        whitelist: false,
         pomodoro: None,
         r#break: None,
         long_break: None,
         cycles: None,
+        block: vec![],
+        allow: vec![],
     };
-    
     let config = flowmode::Config {
-        block_list: None,
-        app_block_list: None, 
-        whitelist: Some(vec!["github.com".to_string()]),
+        block_list: Some(vec!["127.0.0.1 example.com".to_string()]),
+        app_block_list: None,
+        whitelist: None,
         pomodoro_defaults: None,
+        ..flowmode::Config::default()
     };
-    
-    println!("Whitelist test - whitelist mode: {}", start_args.whitelist);
-    println!("Whitelist test - whitelist domains: {:?}", config.whitelist);
-    
-    flowmode::block_websites(&start_args, &config).await.unwrap();
-    
-    let content = fs::read_to_string(temp_path).unwrap();
-    println!("Whitelist content: {}", content);
-    println!("Content length: {}", content.len());
-    println!("Contains facebook.com: {}", content.contains("facebook.com"));
-    println!("Contains twitter.com: {}", content.contains("twitter.com"));
-    
-    // In whitelist mode, we should see blocked social media sites
-    let has_social_media_blocks = content.contains("facebook.com") || 
-                                  content.contains("twitter.com") || 
-                                  content.contains("instagram.com") ||
-                                  content.contains("youtube.com");
-    
-    assert!(has_social_media_blocks, 
-            "Expected social media blocking to occur in whitelist mode.\nActual content: '{}'", content);
-    
-    // GitHub should not be explicitly blocked (it should be whitelisted)
-    assert!(!content.contains("127.0.0.1 github.com"), "GitHub should be whitelisted");
-    
-    // Clean up - restore previous env var if it existed
-    if let Some(val) = old_env {
-        std::env::set_var("FLOWMODE_TEST_HOSTS_FILE", val);
-    } else {
-        std::env::remove_var("FLOWMODE_TEST_HOSTS_FILE");
-    }
+
+    // Blocking twice must not stack two blocks
+    flowmode::block_websites(&mut blockers, &start_args, &config).await.unwrap();
+    flowmode::block_websites(&mut blockers, &start_args, &config).await.unwrap();
+    let blocked = fs::read_to_string(temp_path).unwrap();
+    assert_eq!(blocked.matches("# BEGIN flowmode").count(), 1);
+    assert!(blocked.contains("# BEGIN flowmode\n127.0.0.1 example.com\n::1 example.com\n# END flowmode\n"));
+
+    // Another tool edits the hosts file while the session runs
+    fs::write(temp_path, format!("{}10.0.0.5 build-server\n", blocked)).unwrap();
+
+    flowmode::unblock_websites(&mut blockers, &config).await.unwrap();
+    assert_eq!(fs::read_to_string(temp_path).unwrap(), "127.0.0.1 localhost\n10.0.0.5 build-server\n");
+
+    // Unblocking again is harmless
+    flowmode::unblock_websites(&mut blockers, &config).await.unwrap();
+    assert_eq!(fs::read_to_string(temp_path).unwrap(), "127.0.0.1 localhost\n10.0.0.5 build-server\n");
 }
 
 #[tokio::test]
@@ -198,6 +145,8 @@ async fn test_duration_parsing() {
         r#break: Some("5m".to_string()),
         long_break: Some("15m".to_string()),
         cycles: Some(2),
+        block: vec![],
+        allow: vec![],
     };
     
     // Test that duration parsing doesn't panic
@@ -245,6 +194,7 @@ async fn test_csv_logging() {
 
 #[tokio::test]
 async fn test_stop_command() {
+    let _guard = TEST_MUTEX.lock().await;
     // Test that stop command doesn't panic when no session is running
     let stop_args = StopArgs {};
     let result = run(CliCommand::Stop(stop_args)).await;
@@ -272,4 +222,872 @@ async fn test_slack_webhook_error_handling() {
     let invalid_url = "not-a-valid-url";
     let result = flowmode::post_to_slack(invalid_url, "test message").await;
     assert!(result.is_err());
-}
\ No newline at end of file
+}
+// Launches `flowmode start` in `dir`, with its runtime and data files kept in
+// `dir` as well, against a private hosts file and waits
+// until the session has started its first phase.
+async fn spawn_session(dir: &std::path::Path, extra_args: &[&str]) -> std::process::Child {
+    let hosts_path = dir.join("hosts");
+    if !hosts_path.exists() {
+        fs::write(&hosts_path, "127.0.0.1 localhost\n").unwrap();
+    }
+
+    let session = std::process::Command::new(env!("CARGO_BIN_EXE_flowmode"))
+        .args(["start", "--duration", "10m", "--task", "Cross process"])
+        .args(extra_args)
+        .current_dir(dir)
+        .env("FLOWMODE_HOME", dir)
+        .env("FLOWMODE_TEST_HOSTS_FILE", &hosts_path)
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // The state is saved once before the session is fully set up; the first
+    // phase gives it a deadline
+    let started = || {
+        let state = fs::read_to_string(dir.join("flowmode.state.json")).unwrap_or_default();
+        serde_json::from_str::<serde_json::Value>(&state).is_ok_and(|state| state["phase_deadline"] != state["started_at"])
+    };
+    for _ in 0..100 {
+        if started() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(started(), "Session never started");
+    session
+}
+
+fn flowmode_command(dir: &std::path::Path, args: &[&str]) -> std::process::Output {
+    std::process::Command::new(env!("CARGO_BIN_EXE_flowmode"))
+        .args(args)
+        .current_dir(dir)
+        .env("FLOWMODE_HOME", dir)
+        .env("FLOWMODE_TEST_HOSTS_FILE", dir.join("hosts"))
+        .output()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_stop_from_another_process() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+
+    let stop = flowmode_command(temp_dir.path(), &["stop"]);
+    assert!(stop.status.success(), "Stop failed: {}", String::from_utf8_lossy(&stop.stderr));
+
+    let status = session.wait().unwrap();
+    assert!(status.success());
+
+    // Exactly one complete record, written by the session itself
+    let log = fs::read_to_string(temp_dir.path().join("log.csv")).unwrap();
+    assert_eq!(log.lines().count(), 1);
+    assert_eq!(log.trim_end().split(',').count(), 4, "Unexpected log record: {}", log);
+    assert_eq!(fs::read_to_string(temp_dir.path().join("hosts")).unwrap(), "127.0.0.1 localhost\n");
+    assert!(!temp_dir.path().join("flowmode.pid").exists());
+}
+
+#[tokio::test]
+async fn test_status_of_running_session() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mut session = spawn_session(temp_dir.path(), &["--pomodoro", "3m", "--break", "1m", "--cycles", "2"]).await;
+
+    let output = flowmode_command(temp_dir.path(), &["status", "--json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["running"], true);
+    assert_eq!(report["stale"], false);
+    assert_eq!(report["session"]["task"], "Cross process");
+    assert_eq!(report["session"]["phase"], "work");
+    assert_eq!(report["session"]["cycle"], 1);
+    assert_eq!(report["session"]["total_cycles"], 2);
+    assert!(report["remaining_seconds"].as_i64().unwrap() > 0);
+
+    flowmode_command(temp_dir.path(), &["stop"]);
+    session.wait().unwrap();
+
+    let output = flowmode_command(temp_dir.path(), &["status", "--json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["running"], false);
+    assert_eq!(report["stale"], false);
+}
+
+#[tokio::test]
+async fn test_status_detects_stale_state() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+
+    // Simulate a crash: the process dies without any teardown
+    session.kill().unwrap();
+    session.wait().unwrap();
+
+    let output = flowmode_command(temp_dir.path(), &["status", "--json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["running"], false);
+    assert_eq!(report["stale"], true);
+}
+
+#[tokio::test]
+async fn test_pause_and_resume() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+    let hosts_path = temp_dir.path().join("hosts");
+    let blocked_hosts = fs::read_to_string(&hosts_path).unwrap();
+    assert!(blocked_hosts.contains("facebook.com"));
+
+    let pause = flowmode_command(temp_dir.path(), &["pause", "--lift-blocks"]);
+    assert!(pause.status.success(), "Pause failed: {}", String::from_utf8_lossy(&pause.stderr));
+    assert_eq!(fs::read_to_string(&hosts_path).unwrap(), "127.0.0.1 localhost\n");
+
+    // Pausing twice is rejected
+    assert!(!flowmode_command(temp_dir.path(), &["pause"]).status.success());
+
+    let output = flowmode_command(temp_dir.path(), &["status", "--json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["session"]["paused"]["blocks_lifted"], true);
+    let frozen = report["remaining_seconds"].as_i64().unwrap();
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let output = flowmode_command(temp_dir.path(), &["status", "--json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["remaining_seconds"].as_i64().unwrap(), frozen);
+
+    let resume = flowmode_command(temp_dir.path(), &["resume"]);
+    assert!(resume.status.success(), "Resume failed: {}", String::from_utf8_lossy(&resume.stderr));
+    assert!(fs::read_to_string(&hosts_path).unwrap().contains("facebook.com"));
+    assert!(!flowmode_command(temp_dir.path(), &["resume"]).status.success());
+
+    flowmode_command(temp_dir.path(), &["stop"]);
+    session.wait().unwrap();
+
+    let log = fs::read_to_string(temp_dir.path().join("log.csv")).unwrap();
+    let paused_secs: u64 = log.trim_end().rsplit(',').next().unwrap().parse().unwrap();
+    assert!(paused_secs >= 1, "Pause was not recorded: {}", log);
+
+    let events = fs::read_to_string(temp_dir.path().join("events.csv")).unwrap();
+    assert!(events.contains(",pause,blocks lifted"));
+    assert!(events.contains(",resume,paused for "));
+}
+
+#[tokio::test]
+async fn test_pause_without_session() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let output = flowmode_command(temp_dir.path(), &["pause"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No flow mode session is running"));
+}
+
+#[tokio::test]
+async fn test_extend_and_shorten() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mut session = spawn_session(temp_dir.path(), &["--pomodoro", "3m", "--break", "1m", "--cycles", "2"]).await;
+
+    let remaining = |dir: &std::path::Path| {
+        let output = flowmode_command(dir, &["status", "--json"]);
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        (report["remaining_seconds"].as_i64().unwrap(), report["session"]["total_cycles"].as_u64().unwrap())
+    };
+
+    let (before, cycles) = remaining(temp_dir.path());
+    assert_eq!(cycles, 2);
+
+    let extend = flowmode_command(temp_dir.path(), &["extend", "10m"]);
+    assert!(extend.status.success(), "Extend failed: {}", String::from_utf8_lossy(&extend.stderr));
+    let (after, _) = remaining(temp_dir.path());
+    assert!(after >= before + 590, "Expected about ten more minutes: {} -> {}", before, after);
+
+    // Shrinking the overall budget drops the second cycle
+    let shorten = flowmode_command(temp_dir.path(), &["shorten", "--session", "6m"]);
+    assert!(shorten.status.success(), "Shorten failed: {}", String::from_utf8_lossy(&shorten.stderr));
+    assert_eq!(remaining(temp_dir.path()).1, 1);
+
+    // Cutting the last phase short ends the session on its own
+    flowmode_command(temp_dir.path(), &["shorten", "1h"]);
+    let status = session.wait().unwrap();
+    assert!(status.success());
+
+    let events = fs::read_to_string(temp_dir.path().join("events.csv")).unwrap();
+    assert!(events.contains(",extend,phase +600s"));
+    assert!(events.contains(",shorten,session -360s"));
+    assert!(events.contains(",shorten,phase -3600s"));
+    assert_eq!(fs::read_to_string(temp_dir.path().join("log.csv")).unwrap().lines().count(), 1);
+}
+
+#[tokio::test]
+async fn test_recover_after_crash() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+    let hosts_path = temp_dir.path().join("hosts");
+    assert!(fs::read_to_string(&hosts_path).unwrap().contains("facebook.com"));
+
+    session.kill().unwrap();
+    session.wait().unwrap();
+
+    let recover = flowmode_command(temp_dir.path(), &["recover"]);
+    assert!(recover.status.success(), "Recover failed: {}", String::from_utf8_lossy(&recover.stderr));
+
+    assert_eq!(fs::read_to_string(&hosts_path).unwrap(), "127.0.0.1 localhost\n");
+    assert!(!temp_dir.path().join("flowmode.pid").exists());
+    assert!(!temp_dir.path().join("flowmode.state.json").exists());
+    let log = fs::read_to_string(temp_dir.path().join("log.csv")).unwrap();
+    assert!(log.ends_with(",0,aborted\n"), "Dangling record was not closed: {}", log);
+    assert!(fs::read_to_string(temp_dir.path().join("events.csv")).unwrap().contains(",aborted,"));
+
+    // A second run has nothing left to do
+    let recover = flowmode_command(temp_dir.path(), &["recover"]);
+    assert!(String::from_utf8_lossy(&recover.stdout).contains("Nothing to recover"));
+}
+
+#[tokio::test]
+async fn test_start_recovers_orphaned_session() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mut crashed = spawn_session(temp_dir.path(), &[]).await;
+    crashed.kill().unwrap();
+    crashed.wait().unwrap();
+    fs::remove_file(temp_dir.path().join("flowmode.state.json")).unwrap();
+
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+    flowmode_command(temp_dir.path(), &["stop"]);
+    session.wait().unwrap();
+
+    let log = fs::read_to_string(temp_dir.path().join("log.csv")).unwrap();
+    let records: Vec<&str> = log.lines().collect();
+    assert_eq!(records.len(), 2, "Unexpected log: {}", log);
+    assert!(records[0].ends_with(",aborted"));
+    assert!(!records[1].ends_with(",aborted"));
+    assert_eq!(fs::read_to_string(temp_dir.path().join("hosts")).unwrap(), "127.0.0.1 localhost\n");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_sigterm_runs_teardown() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+
+    send_signal(&session, nix::sys::signal::Signal::SIGTERM);
+    assert!(session.wait().unwrap().success());
+
+    assert_eq!(fs::read_to_string(temp_dir.path().join("hosts")).unwrap(), "127.0.0.1 localhost\n");
+    let log = fs::read_to_string(temp_dir.path().join("log.csv")).unwrap();
+    assert_eq!(log.trim_end().split(',').count(), 4, "Unexpected log record: {}", log);
+    assert!(fs::read_to_string(temp_dir.path().join("events.csv")).unwrap().contains(",signal,SIGTERM"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_strict_mode_confirms_interrupt() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("config.toml"), "block_list = [\"127.0.0.1 example.com\"]\nsignal_policy = \"confirm\"\n").unwrap();
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+
+    // The first Ctrl+C only asks for confirmation
+    send_signal(&session, nix::sys::signal::Signal::SIGINT);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(session.try_wait().unwrap().is_none(), "Session ended without confirmation");
+    assert!(fs::read_to_string(temp_dir.path().join("hosts")).unwrap().contains("example.com"));
+
+    send_signal(&session, nix::sys::signal::Signal::SIGINT);
+    assert!(session.wait().unwrap().success());
+    assert_eq!(fs::read_to_string(temp_dir.path().join("hosts")).unwrap(), "127.0.0.1 localhost\n");
+}
+
+// Polls `condition` for up to five seconds.
+async fn wait_for(mut condition: impl FnMut() -> bool) -> bool {
+    for _ in 0..50 {
+        if condition() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    condition()
+}
+
+#[tokio::test]
+async fn test_tampered_block_is_reapplied() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+    let hosts_path = temp_dir.path().join("hosts");
+    let events_path = temp_dir.path().join("events.csv");
+
+    fs::write(&hosts_path, "127.0.0.1 localhost\n").unwrap();
+    assert!(wait_for(|| fs::read_to_string(&hosts_path).unwrap().contains("facebook.com")).await, "Block was not reapplied");
+    let events = fs::read_to_string(&events_path).unwrap();
+    assert!(events.contains(",tamper,hosts file"), "Unexpected events: {}", events);
+    assert!(events.trim_end().ends_with("reapplied"));
+
+    flowmode_command(temp_dir.path(), &["stop"]);
+    assert!(session.wait().unwrap().success());
+    assert_eq!(fs::read_to_string(&hosts_path).unwrap(), "127.0.0.1 localhost\n");
+}
+
+#[tokio::test]
+async fn test_tamper_policy_log() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("config.toml"), "block_list = [\"example.com\"]\ntamper_policy = \"log\"\n").unwrap();
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+    let hosts_path = temp_dir.path().join("hosts");
+    let events_path = temp_dir.path().join("events.csv");
+
+    // Removing a single entry counts as tampering too
+    let edited: String = fs::read_to_string(&hosts_path).unwrap().lines().filter(|line| *line != "::1 www.example.com").map(|line| format!("{}\n", line)).collect();
+    fs::write(&hosts_path, &edited).unwrap();
+    assert!(wait_for(|| fs::read_to_string(&events_path).is_ok_and(|events| events.contains(",tamper,"))).await, "Tampering was not recorded");
+    assert_eq!(fs::read_to_string(&hosts_path).unwrap(), edited);
+
+    // Further changes while the block is still off are not recorded again
+    fs::write(&hosts_path, format!("{}10.0.0.5 build-server\n", edited)).unwrap();
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let events = fs::read_to_string(&events_path).unwrap();
+    assert_eq!(events.matches(",tamper,").count(), 1, "Unexpected events: {}", events);
+
+    flowmode_command(temp_dir.path(), &["stop"]);
+    assert!(session.wait().unwrap().success());
+}
+
+// Serves `body` with an ETag over HTTP for `connections` requests, answering
+// 304 when the client already has it, then goes offline. Returns the URL and the
+// request headers it received.
+fn spawn_list_server(body: &'static str, connections: usize) -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+    use std::io::{BufRead, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hosts.txt", listener.local_addr().unwrap());
+    let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let seen = requests.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().take(connections) {
+            let mut stream = stream.unwrap();
+            let mut headers = String::new();
+            let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                    break;
+                }
+                headers.push_str(&line.to_ascii_lowercase());
+            }
+            let response = if headers.contains("if-none-match: \"v1\"") {
+                "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n".to_string()
+            } else {
+                format!("HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body)
+            };
+            stream.write_all(response.as_bytes()).unwrap();
+            seen.lock().unwrap().push(headers);
+        }
+    });
+    (url, requests)
+}
+
+#[tokio::test]
+async fn test_block_list_sources() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let (url, requests) = spawn_list_server("# remote list\n0.0.0.0 remote-one.com remote-two.com\n127.0.0.1 localhost\n", 2);
+    fs::write(temp_dir.path().join("local.txt"), "local.example\n").unwrap();
+    fs::write(
+        temp_dir.path().join("config.toml"),
+        format!("block_list = [\"example.com\"]\nsubdomains = []\nblock_list_sources = [\"{}\", \"local.txt\"]\n", url),
+    )
+    .unwrap();
+
+    let update = flowmode_command(temp_dir.path(), &["lists", "update"]);
+    assert!(update.status.success(), "Update failed: {}", String::from_utf8_lossy(&update.stderr));
+    assert!(String::from_utf8_lossy(&update.stdout).contains(": 2 domains, downloaded"));
+    assert!(String::from_utf8_lossy(&update.stdout).contains("3 domains from 2 block lists"));
+
+    // The second update sends the cached ETag and keeps the cached copy
+    let update = flowmode_command(temp_dir.path(), &["lists", "update"]);
+    assert!(String::from_utf8_lossy(&update.stdout).contains(": 2 domains, unchanged"));
+    assert!(requests.lock().unwrap()[1].contains("if-none-match: \"v1\""));
+
+    // With the server gone the cached copy is used, and the update reports the failure
+    let update = flowmode_command(temp_dir.path(), &["lists", "update"]);
+    assert!(!update.status.success());
+    assert!(String::from_utf8_lossy(&update.stderr).contains("offline, using the copy from"));
+
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+    let hosts = fs::read_to_string(temp_dir.path().join("hosts")).unwrap();
+    for domain in ["example.com", "remote-one.com", "remote-two.com", "local.example"] {
+        assert!(hosts.contains(&format!("127.0.0.1 {}\n", domain)), "{} is not blocked:\n{}", domain, hosts);
+    }
+    assert_eq!(hosts.matches("localhost").count(), 1);
+
+    flowmode_command(temp_dir.path(), &["stop"]);
+    assert!(session.wait().unwrap().success());
+}
+
+#[tokio::test]
+async fn test_block_list_paths_are_relative_to_config() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join("lists")).unwrap();
+    fs::write(temp_dir.path().join("lists").join("local.txt"), "local.example\n").unwrap();
+    fs::write(temp_dir.path().join("config.toml"), "block_list_sources = [\"lists/local.txt\"]\n").unwrap();
+
+    // The list is found next to the config.toml that names it
+    let update = flowmode_command(temp_dir.path(), &["lists", "update"]);
+    assert!(update.status.success(), "Update failed: {}", String::from_utf8_lossy(&update.stderr));
+    assert!(String::from_utf8_lossy(&update.stdout).contains("lists/local.txt: 1 domains, local file"));
+}
+
+#[tokio::test]
+async fn test_block_categories() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("config.toml"),
+        "block_list = [\"example.com\"]\nsubdomains = []\n\n[categories]\nsocial = [\"social.example\", \"chat.example\"]\nnews = [\"news.example\"]\n",
+    )
+    .unwrap();
+
+    // An unknown category is refused before anything is blocked
+    let output = flowmode_command(temp_dir.path(), &["start", "--duration", "10m", "--block", "sport"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Available categories: news, social"));
+
+    let mut session = spawn_session(temp_dir.path(), &["--block", "social,extra.example", "--allow", "chat.example"]).await;
+    let hosts = fs::read_to_string(temp_dir.path().join("hosts")).unwrap();
+    for domain in ["example.com", "social.example", "extra.example"] {
+        assert!(hosts.contains(&format!("127.0.0.1 {}\n", domain)), "{} is not blocked:\n{}", domain, hosts);
+    }
+    assert!(!hosts.contains("chat.example") && !hosts.contains("news.example"));
+
+    let output = flowmode_command(temp_dir.path(), &["status", "--json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["session"]["categories"], serde_json::json!(["social"]));
+
+    flowmode_command(temp_dir.path(), &["stop"]);
+    assert!(session.wait().unwrap().success());
+    let report = flowmode_command(temp_dir.path(), &["report"]);
+    assert!(String::from_utf8_lossy(&report.stdout).contains("Categories: social"));
+}
+
+#[tokio::test]
+async fn test_browser_policy_backend() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let firefox_dir = temp_dir.path().join("firefox");
+    let chromium_dir = temp_dir.path().join("chromium");
+    fs::write(
+        temp_dir.path().join("config.toml"),
+        format!(
+            "block_list = [\"example.com\"]\nblocking_backends = [\"hosts\", \"browser\"]\n\n[browser_policy]\nfirefox_dir = {:?}\nchromium_dirs = [{:?}]\n",
+            firefox_dir.display().to_string(),
+            chromium_dir.display().to_string()
+        ),
+    )
+    .unwrap();
+
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+    let policies = fs::read_to_string(firefox_dir.join("policies.json")).unwrap();
+    assert!(policies.contains("*://*.example.com/*"), "{}", policies);
+    let policies = fs::read_to_string(chromium_dir.join("flowmode.json")).unwrap();
+    assert!(policies.contains("\"example.com\""), "{}", policies);
+    assert!(fs::read_to_string(temp_dir.path().join("hosts")).unwrap().contains("127.0.0.1 example.com\n"));
+
+    flowmode_command(temp_dir.path(), &["stop"]);
+    assert!(session.wait().unwrap().success());
+    assert_eq!(fs::read_dir(&firefox_dir).unwrap().count(), 0);
+    assert_eq!(fs::read_dir(&chromium_dir).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn test_block_page() {
+    use std::io::{Read, Write};
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let template = temp_dir.path().join("block.html");
+    fs::write(&template, "<p>{{domain}}: {{task}} for {{remaining}}</p>").unwrap();
+    fs::write(
+        temp_dir.path().join("config.toml"),
+        format!(
+            "block_list = [\"example.com\"]\nredirect_addresses = [\"127.0.0.1\"]\n\n[block_page]\nenabled = true\nport = {}\ntemplate = {:?}\n",
+            port,
+            template.display().to_string()
+        ),
+    )
+    .unwrap();
+
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream.write_all(b"GET /feed HTTP/1.1\r\nHost: www.Example.com:80\r\nUser-Agent: test\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.contains("Cache-Control: no-store"));
+    assert!(response.contains("<p>www.example.com: Cross process for 09:"), "{}", response);
+
+    flowmode_command(temp_dir.path(), &["stop"]);
+    assert!(session.wait().unwrap().success());
+    assert!(std::net::TcpStream::connect(("127.0.0.1", port)).is_err(), "The block page outlived the session");
+}
+
+#[tokio::test]
+async fn test_scheduled_blocking() {
+    use chrono::Datelike;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let tomorrow = chrono::Local::now().weekday().succ().to_string();
+    fs::write(
+        temp_dir.path().join("config.toml"),
+        format!(
+            "block_list = [\"example.com\", \"later.example\"]\nsubdomains = []\n\n[categories]\nnews = [\"news.example\"]\n\n\
+             [schedules]\nnews = [{{ hours = \"00:00-00:00\" }}]\n\"later.example\" = [{{ days = [\"{}\"] }}]\n",
+            tomorrow
+        ),
+    )
+    .unwrap();
+
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+    let hosts = fs::read_to_string(temp_dir.path().join("hosts")).unwrap();
+    assert!(hosts.contains("127.0.0.1 example.com\n") && hosts.contains("127.0.0.1 news.example\n"), "{}", hosts);
+    assert!(!hosts.contains("later.example"), "{}", hosts);
+
+    flowmode_command(temp_dir.path(), &["stop"]);
+    assert!(session.wait().unwrap().success());
+    let events = fs::read_to_string(temp_dir.path().join("events.csv")).unwrap();
+    assert!(events.contains(",schedule,news blocked"), "{}", events);
+}
+
+#[tokio::test]
+async fn test_block_overrides_closed_schedule() {
+    use chrono::Datelike;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let tomorrow = chrono::Local::now().weekday().succ().to_string();
+    fs::write(
+        temp_dir.path().join("config.toml"),
+        format!(
+            "block_list = [\"example.com\"]\nsubdomains = []\n\n[categories]\nforums = [\"forum.example\"]\n\n\
+             [schedules]\n\"later.example\" = [{{ days = [\"{day}\"] }}]\n\"forum.example\" = [{{ days = [\"{day}\"] }}]\n",
+            day = tomorrow
+        ),
+    )
+    .unwrap();
+
+    // Both windows are closed today, but the session asked for both domains
+    let mut session = spawn_session(temp_dir.path(), &["--block", "later.example,forums"]).await;
+    let hosts = fs::read_to_string(temp_dir.path().join("hosts")).unwrap();
+    assert!(hosts.contains("127.0.0.1 later.example\n"), "{}", hosts);
+    assert!(hosts.contains("127.0.0.1 forum.example\n"), "{}", hosts);
+
+    flowmode_command(temp_dir.path(), &["stop"]);
+    assert!(session.wait().unwrap().success());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_relaunched_app_is_closed() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let app = temp_dir.path().join("flowmode-app");
+    fs::copy("/bin/sleep", &app).unwrap();
+    fs::write(
+        temp_dir.path().join("config.toml"),
+        "block_list = [\"example.com\"]\napp_block_list = [\"flowmode-app\"]\napp_watch_interval = \"200ms\"\n",
+    )
+    .unwrap();
+
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+    let mut relaunched = std::process::Command::new(&app).arg("60").spawn().unwrap();
+    assert!(wait_for(|| relaunched.try_wait().unwrap().is_some()).await, "The relaunched app kept running");
+
+    let output = flowmode_command(temp_dir.path(), &["status", "--json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["session"]["apps_killed"][0]["pid"], relaunched.id());
+
+    flowmode_command(temp_dir.path(), &["stop"]);
+    assert!(session.wait().unwrap().success());
+    let events = fs::read_to_string(temp_dir.path().join("events.csv")).unwrap();
+    assert!(events.contains(&format!(",app,flowmode-app (PID {}) killed\n", relaunched.id())), "{}", events);
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_failed_start_is_undone() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let app = temp_dir.path().join("flowmode-abort");
+    fs::copy("/bin/sleep", &app).unwrap();
+    fs::write(
+        temp_dir.path().join("config.toml"),
+        "block_list = [\"example.com\"]\napp_block_list = [\"flowmode-abort\"]\napp_block_policy = \"freeze\"\n",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("hosts"), "127.0.0.1 localhost\n").unwrap();
+    let mut running = std::process::Command::new(&app).arg("60").spawn().unwrap();
+    let comm = format!("/proc/{}/comm", running.id());
+    assert!(wait_for(|| fs::read_to_string(&comm).is_ok_and(|name| name.trim() == "flowmode-abort")).await);
+    // The PID file cannot be written where a directory is in the way
+    fs::create_dir(temp_dir.path().join("flowmode.pid")).unwrap();
+
+    let start = flowmode_command(temp_dir.path(), &["start", "--duration", "10m"]);
+    assert!(!start.status.success());
+    assert!(String::from_utf8_lossy(&start.stdout).contains("Froze process: flowmode-abort"));
+
+    // Blocking, freezing and the state file are all undone
+    assert_eq!(fs::read_to_string(temp_dir.path().join("hosts")).unwrap(), "127.0.0.1 localhost\n");
+    let stat = fs::read_to_string(format!("/proc/{}/stat", running.id())).unwrap();
+    assert_ne!(stat.rsplit(") ").next().unwrap().split_whitespace().next().unwrap(), "T");
+    assert!(!temp_dir.path().join("flowmode.state.json").exists());
+    assert!(!temp_dir.path().join("log.csv").exists());
+
+    running.kill().unwrap();
+    running.wait().unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_block_applications_reports_frozen_processes() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let app = temp_dir.path().join("flowmode-lib");
+    fs::copy("/bin/sleep", &app).unwrap();
+    let mut running = std::process::Command::new(&app).arg("60").spawn().unwrap();
+    // Until the exec the child still carries the name of the test binary
+    let comm = format!("/proc/{}/comm", running.id());
+    assert!(wait_for(|| fs::read_to_string(&comm).is_ok_and(|name| name.trim() == "flowmode-lib")).await);
+    let config = flowmode::Config {
+        app_block_list: Some(vec!["flowmode-lib".to_string()]),
+        app_block_policy: Some(flowmode::AppBlockPolicy::Freeze),
+        ..flowmode::Config::default()
+    };
+
+    // The caller learns which processes were frozen and can let them continue
+    let enforcements = flowmode::block_applications(&config).await.unwrap();
+    let frozen = enforcements.iter().find(|enforcement| enforcement.pid == running.id()).expect("The app was not handled");
+    assert_eq!(frozen.outcome, flowmode::AppOutcome::Frozen);
+    assert!(flowmode::thaw(&frozen.process()));
+
+    running.kill().unwrap();
+    running.wait().unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_frozen_apps_are_resumed() {
+    // The state letter from /proc: "T" while stopped
+    fn process_state(pid: u32) -> String {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap();
+        stat.rsplit(") ").next().unwrap().split_whitespace().next().unwrap().to_string()
+    }
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let app = temp_dir.path().join("flowmode-app");
+    fs::copy("/bin/sleep", &app).unwrap();
+    fs::write(
+        temp_dir.path().join("config.toml"),
+        "block_list = [\"example.com\"]\napp_block_list = [\"flowmode-app\"]\napp_block_policy = \"freeze\"\napp_watch_interval = \"200ms\"\n",
+    )
+    .unwrap();
+    let mut running = std::process::Command::new(&app).arg("60").spawn().unwrap();
+
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+    assert_eq!(process_state(running.id()), "T");
+    let output = flowmode_command(temp_dir.path(), &["status", "--json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["session"]["apps_frozen"][0]["pid"], running.id());
+    assert_eq!(report["session"]["apps_killed"], serde_json::json!([]));
+
+    // Continuing it by hand does not get around the block
+    send_signal(&running, nix::sys::signal::Signal::SIGCONT);
+    assert!(wait_for(|| process_state(running.id()) == "T").await, "The app was not frozen again");
+
+    flowmode_command(temp_dir.path(), &["stop"]);
+    assert!(session.wait().unwrap().success());
+    assert_ne!(process_state(running.id()), "T");
+
+    // Recovery after a crash lets them continue as well
+    let mut session = spawn_session(temp_dir.path(), &[]).await;
+    assert_eq!(process_state(running.id()), "T");
+    session.kill().unwrap();
+    session.wait().unwrap();
+    let recover = flowmode_command(temp_dir.path(), &["recover"]);
+    assert!(recover.status.success(), "Recover failed: {}", String::from_utf8_lossy(&recover.stderr));
+    assert_ne!(process_state(running.id()), "T");
+    running.kill().unwrap();
+    running.wait().unwrap();
+
+    let events = fs::read_to_string(temp_dir.path().join("events.csv")).unwrap();
+    assert_eq!(events.matches(&format!(",app,flowmode-app (PID {}) frozen\n", running.id())).count(), 3, "{}", events);
+}
+
+#[tokio::test]
+async fn test_invalid_durations_are_rejected_before_blocking() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("hosts"), "127.0.0.1 localhost\n").unwrap();
+    fs::write(temp_dir.path().join("config.toml"), "block_list = [\"example.com\"]\n").unwrap();
+
+    let start = flowmode_command(temp_dir.path(), &["start", "--duration", "10m", "--pomodoro", "soon"]);
+    assert!(!start.status.success());
+    assert!(String::from_utf8_lossy(&start.stderr).contains("Invalid pomodoro duration 'soon'"));
+    assert_eq!(fs::read_to_string(temp_dir.path().join("hosts")).unwrap(), "127.0.0.1 localhost\n");
+    assert!(!temp_dir.path().join("flowmode.state.json").exists());
+}
+
+#[cfg(unix)]
+fn send_signal(process: &std::process::Child, signal: nix::sys::signal::Signal) {
+    let pid = nix::unistd::Pid::from_raw(process.id() as i32);
+    nix::sys::signal::kill(pid, signal).unwrap();
+}
+
+#[tokio::test]
+async fn test_stop_from_another_directory() {
+    let home = tempfile::TempDir::new().unwrap();
+    let elsewhere = tempfile::TempDir::new().unwrap();
+    let mut session = spawn_session(home.path(), &[]).await;
+
+    let stop = std::process::Command::new(env!("CARGO_BIN_EXE_flowmode"))
+        .arg("stop")
+        .current_dir(elsewhere.path())
+        .env("FLOWMODE_HOME", home.path())
+        .env("FLOWMODE_TEST_HOSTS_FILE", home.path().join("hosts"))
+        .output()
+        .unwrap();
+    assert!(stop.status.success(), "Stop failed: {}", String::from_utf8_lossy(&stop.stderr));
+    assert!(session.wait().unwrap().success());
+
+    assert_eq!(fs::read_to_string(home.path().join("hosts")).unwrap(), "127.0.0.1 localhost\n");
+    assert_eq!(fs::read_to_string(home.path().join("log.csv")).unwrap().lines().count(), 1);
+    assert_eq!(fs::read_dir(elsewhere.path()).unwrap().count(), 0, "Files were written to the working directory");
+}
+
+#[tokio::test]
+async fn test_legacy_files_are_migrated() {
+    let home = tempfile::TempDir::new().unwrap();
+    let legacy_dir = tempfile::TempDir::new().unwrap();
+    fs::write(home.path().join("log.csv"), "New,2024-01-02T10:00:00+00:00,2024-01-02T11:00:00+00:00,0\n").unwrap();
+    fs::write(legacy_dir.path().join("log.csv"), "Old,2024-01-01T10:00:00+00:00,2024-01-01T11:00:00+00:00\n").unwrap();
+    fs::write(legacy_dir.path().join("user_hosts.backup"), "127.0.0.1 localhost\n").unwrap();
+    // A CSV of some other program that happens to share the name
+    fs::write(legacy_dir.path().join("events.csv"), "id,name\n1,alice\n").unwrap();
+
+    let report = std::process::Command::new(env!("CARGO_BIN_EXE_flowmode"))
+        .arg("report")
+        .current_dir(legacy_dir.path())
+        .env("FLOWMODE_HOME", home.path())
+        .output()
+        .unwrap();
+    assert!(report.status.success());
+    let output = String::from_utf8_lossy(&report.stdout);
+    assert!(output.contains("Old") && output.contains("New"), "Report is missing sessions: {}", output);
+
+    // The legacy log is appended to the existing one and the backup moved alongside the state
+    let log = fs::read_to_string(home.path().join("log.csv")).unwrap();
+    assert_eq!(log.lines().count(), 2);
+    assert!(home.path().join("user_hosts.backup").exists());
+    assert!(!legacy_dir.path().join("log.csv").exists());
+    assert!(!legacy_dir.path().join("user_hosts.backup").exists());
+    assert_eq!(fs::read_to_string(legacy_dir.path().join("events.csv")).unwrap(), "id,name\n1,alice\n");
+    assert!(!home.path().join("events.csv").exists());
+    assert!(output.contains("does not look like a flowmode log"), "{}", output);
+}
+
+// A DNS query for `name` with the given id and record type (1 = A, 28 = AAAA).
+fn dns_query(id: u16, name: &str, qtype: u16) -> Vec<u8> {
+    let mut packet = id.to_be_bytes().to_vec();
+    packet.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.') {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&qtype.to_be_bytes());
+    packet.extend_from_slice(&[0, 1]);
+    packet
+}
+
+async fn dns_exchange(server: std::net::SocketAddr, query: &[u8]) -> Vec<u8> {
+    let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.send_to(query, server).await.unwrap();
+    let mut buffer = [0u8; 512];
+    let (len, _) = tokio::time::timeout(Duration::from_secs(5), socket.recv_from(&mut buffer)).await.unwrap().unwrap();
+    buffer[..len].to_vec()
+}
+
+// Stub upstream resolver that answers every query with 203.0.113.7
+async fn spawn_stub_upstream() -> std::net::SocketAddr {
+    let upstream = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buffer = [0u8; 512];
+        while let Ok((len, client)) = upstream.recv_from(&mut buffer).await {
+            let mut response = buffer[..len].to_vec();
+            response[2] |= 0x80;
+            response[7] = 1;
+            response.extend_from_slice(&[0xC0, 0x0C, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 203, 0, 113, 7]);
+            let _ = upstream.send_to(&response, client).await;
+        }
+    });
+    upstream_addr
+}
+
+// A DNS backend on a free local port that forwards to `upstream`, applied with `plan`.
+fn start_dns_blocker(upstream: std::net::SocketAddr, plan: &flowmode::BlockPlan) -> (flowmode::DnsBlocker, std::net::SocketAddr) {
+    let mut blocker = flowmode::DnsBlocker::new("127.0.0.1:0".parse().unwrap(), upstream);
+    blocker.apply(plan).unwrap();
+    let resolver = blocker.local_addr().unwrap();
+    (blocker, resolver)
+}
+
+#[tokio::test]
+async fn test_allowlist_resolver() {
+    let upstream = spawn_stub_upstream().await;
+    let config = flowmode::Config {
+        whitelist: Some(vec!["github.com".to_string(), "*.rust-lang.org".to_string()]),
+        ..flowmode::Config::default()
+    };
+    let plan = flowmode::BlockPlan::from_config(&config, true);
+    let (mut blocker, resolver) = start_dns_blocker(upstream, &plan);
+    assert!(blocker.verify(&plan).is_ok());
+
+    for (id, name) in [(1, "github.com"), (2, "www.github.com"), (3, "rust-lang.org"), (4, "docs.rust-lang.org")] {
+        let response = dns_exchange(resolver, &dns_query(id, name, 1)).await;
+        assert_eq!(&response[..2], &id.to_be_bytes());
+        assert_eq!(&response[response.len() - 4..], &[203, 0, 113, 7], "{} should be forwarded", name);
+    }
+
+    // Everything else, including look-alike names, is redirected locally
+    for (id, name) in [(5, "facebook.com"), (6, "gist.github.com"), (7, "evilrust-lang.org")] {
+        let response = dns_exchange(resolver, &dns_query(id, name, 1)).await;
+        assert_eq!(&response[..2], &id.to_be_bytes());
+        assert_eq!(response[3] & 0x0F, 0, "Unexpected rcode for {}", name);
+        assert_eq!(&response[response.len() - 4..], &[127, 0, 0, 1], "{} should be blocked", name);
+    }
+    let response = dns_exchange(resolver, &dns_query(8, "facebook.com", 28)).await;
+    let mut loopback_v6 = [0u8; 16];
+    loopback_v6[15] = 1;
+    assert_eq!(&response[response.len() - 16..], &loopback_v6);
+
+    // Reverting stops the resolver
+    blocker.revert().unwrap();
+    assert!(blocker.verify(&plan).is_err());
+    assert!(blocker.local_addr().is_none());
+}
+
+#[tokio::test]
+async fn test_dns_blocking_backend() {
+    let upstream = spawn_stub_upstream().await;
+    let config = flowmode::Config {
+        block_list: Some(vec!["reddit.com".to_string(), "*.youtube.com".to_string(), "0.0.0.0 ads.example.com".to_string()]),
+        dns: Some(flowmode::DnsConfig {
+            listen: Some("127.0.0.1:0".to_string()),
+            upstream: Some(upstream.to_string()),
+        }),
+        blocking_backends: Some(vec![flowmode::BlockingBackend::Dns]),
+        ..flowmode::Config::default()
+    };
+
+    // Only the dns backend is selected, so the hosts file stays untouched
+    let blockers = flowmode::build_blockers(&config, false).unwrap();
+    assert_eq!(blockers.iter().map(|blocker| blocker.describe()).collect::<Vec<_>>(), vec!["DNS resolver on 127.0.0.1:0"]);
+
+    let (mut blocker, resolver) = start_dns_blocker(upstream, &flowmode::BlockPlan::from_config(&config, false));
+    for (id, name) in [(1u16, "reddit.com"), (2, "old.reddit.com"), (3, "youtube.com"), (4, "m.youtube.com"), (5, "ads.example.com")] {
+        let response = dns_exchange(resolver, &dns_query(id, name, 1)).await;
+        assert_eq!(&response[response.len() - 4..], &[127, 0, 0, 1], "{} should be blocked", name);
+    }
+    for (id, name) in [(6u16, "example.com"), (7, "notreddit.com"), (8, "github.com")] {
+        let response = dns_exchange(resolver, &dns_query(id, name, 1)).await;
+        assert_eq!(&response[response.len() - 4..], &[203, 0, 113, 7], "{} should be forwarded", name);
+    }
+
+    blocker.revert().unwrap();
+
+    // The hosts file is the default backend
+    let hosts_only = flowmode::Config { blocking_backends: None, ..config };
+    let blockers = flowmode::build_blockers(&hosts_only, false).unwrap();
+    assert_eq!(blockers.len(), 1);
+    assert!(blockers[0].describe().starts_with("hosts file"));
+}
@@ -0,0 +1,166 @@
+// Tests of the blocking logic against in-memory backends. They need the
+// `test-util` feature, which exposes `MemoryBlocker`:
+//
+//     cargo test --features test-util
+use flowmode::{Blocker, StartArgs};
+use std::fs;
+use tempfile::NamedTempFile;
+
+#[tokio::test]
+async fn test_website_blocking_and_unblocking() {
+    let memory = flowmode::MemoryBlocker::new();
+    let mut blockers: Vec<Box<dyn Blocker>> = vec![Box::new(memory.clone())];
+    
+    // Create test start args with explicit whitelist = false
+    let start_args = StartArgs {
+        duration: "1m".to_string(),
+        task: Some("Test task".to_string()),
+        slack_webhook_url: None,
+        whitelist: false,  // Explicitly false
+        pomodoro: None,
+        r#break: None,
+        long_break: None,
+        cycles: None,
+        block: vec![],
+        allow: vec![],
+    };
+    
+    // Test config with ONLY our blocked site (no defaults)
+    let config = flowmode::Config {
+        block_list: Some(vec!["127.0.0.1 example.com".to_string()]),
+        app_block_list: None,
+        whitelist: None,
+        pomodoro_defaults: None,
+        ..flowmode::Config::default()
+    };
+    
+    // Test blocking
+    let plan = flowmode::block_websites(&mut blockers, &start_args, &config).await.unwrap();
+    assert_eq!(plan.block_list, vec!["127.0.0.1 example.com".to_string()]);
+    assert_eq!(memory.applied(), Some(plan.clone()));
+    assert!(blockers[0].verify(&plan).is_ok());
+    
+    // Test unblocking
+    flowmode::unblock_websites(&mut blockers, &config).await.unwrap();
+    assert_eq!(memory.applied(), None);
+    assert_eq!(memory.calls(), vec!["apply", "revert"]);
+}
+
+#[tokio::test]
+async fn test_block_websites_uses_selection_and_block_lists() {
+    let list = NamedTempFile::new().unwrap();
+    fs::write(list.path(), "0.0.0.0 tracker.example\n0.0.0.0 news.example\n").unwrap();
+    let memory = flowmode::MemoryBlocker::new();
+    let mut blockers: Vec<Box<dyn Blocker>> = vec![Box::new(memory.clone())];
+
+    let start_args = StartArgs {
+        duration: "1m".to_string(),
+        task: None,
+        slack_webhook_url: None,
+        whitelist: false,
+        pomodoro: None,
+        r#break: None,
+        long_break: None,
+        cycles: None,
+        block: vec!["social".to_string(), "extra.example".to_string()],
+        allow: vec!["news.example".to_string()],
+    };
+    let config = flowmode::Config {
+        block_list: Some(vec!["example.com".to_string()]),
+        block_list_sources: Some(vec![list.path().display().to_string()]),
+        categories: Some([("social".to_string(), vec!["reddit.com".to_string()])].into_iter().collect()),
+        app_block_list: None,
+        ..flowmode::Config::default()
+    };
+
+    // block_list, the category, the domain and the imported list, less --allow
+    flowmode::block_websites(&mut blockers, &start_args, &config).await.unwrap();
+    let applied = memory.applied().unwrap();
+    assert_eq!(applied.block_list, vec!["example.com", "reddit.com", "extra.example", "tracker.example"]);
+
+    flowmode::unblock_websites(&mut blockers, &config).await.unwrap();
+    assert_eq!(memory.applied(), None);
+}
+
+#[tokio::test]
+async fn test_whitelist_mode() {
+    let memory = flowmode::MemoryBlocker::new();
+    let mut blockers: Vec<Box<dyn Blocker>> = vec![Box::new(memory.clone())];
+    
+    let start_args = StartArgs {
+        duration: "1m".to_string(),
+        task: Some("Test task".to_string()),
+        slack_webhook_url: None,
+        whitelist: true,  // Explicitly true for whitelist mode
+        pomodoro: None,
+        r#break: None,
+        long_break: None,
+        cycles: None,
+        block: vec![],
+        allow: vec![],
+    };
+    
+    let config = flowmode::Config {
+        block_list: None,
+        app_block_list: None, 
+        whitelist: Some(vec!["github.com".to_string()]),
+        pomodoro_defaults: None,
+        ..flowmode::Config::default()
+    };
+    
+    // Everything but the whitelist is blocked
+    flowmode::block_websites(&mut blockers, &start_args, &config).await.unwrap();
+    let applied = memory.applied().unwrap();
+    assert_eq!(applied.allowlist, Some(vec!["github.com".to_string()]));
+    assert!(applied.block_list.is_empty());
+
+    // Only the local resolver can enforce it; the hosts file is left alone
+    let backends = flowmode::build_blockers(&config, true).unwrap();
+    assert_eq!(backends.len(), 1);
+    assert!(backends[0].describe().starts_with("DNS resolver"));
+}
+
+// A backend whose apply always fails, to check that a failed start is rolled back.
+struct FailingBlocker;
+
+impl Blocker for FailingBlocker {
+    fn describe(&self) -> String {
+        "failing blocker".to_string()
+    }
+
+    fn apply(&mut self, _plan: &flowmode::BlockPlan) -> Result<(), Box<dyn std::error::Error>> {
+        Err("no permission".into())
+    }
+
+    fn verify(&self, _plan: &flowmode::BlockPlan) -> Result<(), Box<dyn std::error::Error>> {
+        Err("not applied".into())
+    }
+
+    fn revert(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_blockers_apply_and_revert_in_memory() {
+    let plan = flowmode::BlockPlan::from_config(&flowmode::Config::default(), false);
+    let first = flowmode::MemoryBlocker::new();
+    let second = flowmode::MemoryBlocker::new();
+    let mut blockers: Vec<Box<dyn Blocker>> = vec![Box::new(first.clone()), Box::new(second.clone())];
+
+    flowmode::apply_blockers(&mut blockers, &plan).unwrap();
+    assert_eq!(first.applied(), Some(plan.clone()));
+    assert!(blockers.iter().all(|blocker| blocker.verify(&plan).is_ok()));
+
+    assert!(flowmode::revert_blockers(&mut blockers).is_empty());
+    assert_eq!(second.applied(), None);
+    assert_eq!(first.calls(), vec!["apply", "revert"]);
+
+    // A backend that fails to apply undoes the ones before it
+    let memory = flowmode::MemoryBlocker::new();
+    let mut blockers: Vec<Box<dyn Blocker>> = vec![Box::new(memory.clone()), Box::new(FailingBlocker)];
+    let error = flowmode::apply_blockers(&mut blockers, &plan).unwrap_err();
+    assert!(error.to_string().contains("failing blocker"));
+    assert_eq!(memory.applied(), None);
+    assert_eq!(memory.calls(), vec!["apply", "revert"]);
+}
@@ -8,9 +8,12 @@ fn test_config_default() {
     assert!(config.app_block_list.is_some());
     assert!(config.pomodoro_defaults.is_some());
     
-    let block_list = config.block_list.unwrap();
-    assert!(block_list.contains(&"127.0.0.1 facebook.com".to_string()));
-    assert!(block_list.contains(&"127.0.0.1 www.facebook.com".to_string()));
+    let block_list = config.block_list.clone().unwrap();
+    assert!(block_list.contains(&"facebook.com".to_string()));
+    let entries = hosts_entries(&BlockPlan::from_config(&config, false));
+    assert!(entries.contains(&("127.0.0.1".to_string(), "facebook.com".to_string())));
+    assert!(entries.contains(&("127.0.0.1".to_string(), "www.facebook.com".to_string())));
+    assert!(entries.contains(&("::1".to_string(), "www.facebook.com".to_string())));
     
     let app_list = config.app_block_list.unwrap();
     assert!(app_list.contains(&"slack.exe".to_string()));
@@ -52,7 +55,7 @@ fn test_cli_parsing() {
         CliCommand::Start(start_args) => {
             assert_eq!(start_args.duration, "30m");
             assert_eq!(start_args.task, Some("Test task".to_string()));
-            assert_eq!(start_args.whitelist, false);
+            assert!(!start_args.whitelist);
         }
         _ => panic!("Expected Start command"),
     }
@@ -99,7 +102,7 @@ fn test_start_args_with_pomodoro() {
             assert_eq!(start_args.r#break, Some("10m".to_string()));
             assert_eq!(start_args.long_break, Some("30m".to_string()));
             assert_eq!(start_args.cycles, Some(3));
-            assert_eq!(start_args.whitelist, true);
+            assert!(start_args.whitelist);
         }
         _ => panic!("Expected Start command"),
     }
@@ -207,4 +210,667 @@ cycles = 2
         assert_eq!(pomodoro.long_break, "20m");
         assert_eq!(pomodoro.cycles, 2);
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_status_args_parsing() {
+    use clap::Parser;
+
+    let cli = Cli::try_parse_from(["flowmode", "status", "--json"]).unwrap();
+    match cli.command {
+        CliCommand::Status(status_args) => assert!(status_args.json),
+        _ => panic!("Expected Status command"),
+    }
+}
+
+#[test]
+fn test_format_remaining() {
+    assert_eq!(format_remaining(chrono::Duration::seconds(125)), "02:05");
+    assert_eq!(format_remaining(chrono::Duration::seconds(3725)), "01:02:05");
+    assert_eq!(format_remaining(chrono::Duration::seconds(-5)), "00:00");
+}
+
+#[test]
+fn test_session_state_serialization() {
+    let now = chrono::Local::now();
+    let state = SessionState {
+        pid: 42,
+        task: "Write docs".to_string(),
+        started_at: now,
+        phase: Phase::ShortBreak,
+        cycle: 2,
+        total_cycles: 4,
+        phase_deadline: now + chrono::Duration::minutes(5),
+        hosts_path: std::path::PathBuf::from("/etc/hosts"),
+        apps_killed: vec![BlockedProcess { name: "slack".to_string(), pid: 7 }],
+        apps_frozen: vec![],
+        paused: None,
+        blockers: vec!["hosts file /etc/hosts".to_string()],
+        categories: vec![],
+    };
+
+    let json = serde_json::to_string(&state).unwrap();
+    assert!(json.contains("\"phase\":\"short_break\""));
+    let deserialized: SessionState = serde_json::from_str(&json).unwrap();
+    assert_eq!(state, deserialized);
+}
+
+#[test]
+fn test_pause_resume_parsing() {
+    use clap::Parser;
+
+    let cli = Cli::try_parse_from(["flowmode", "pause", "--lift-blocks"]).unwrap();
+    match cli.command {
+        CliCommand::Pause(pause_args) => assert!(pause_args.lift_blocks),
+        _ => panic!("Expected Pause command"),
+    }
+
+    let cli = Cli::try_parse_from(["flowmode", "resume"]).unwrap();
+    assert!(matches!(cli.command, CliCommand::Resume));
+
+    let cli = Cli::try_parse_from(["flowmode", "recover"]).unwrap();
+    assert!(matches!(cli.command, CliCommand::Recover));
+}
+
+#[test]
+fn test_paused_state_freezes_remaining_time() {
+    let now = chrono::Local::now();
+    let state = SessionState {
+        pid: 42,
+        task: "Write docs".to_string(),
+        started_at: now,
+        phase: Phase::Work,
+        cycle: 1,
+        total_cycles: 4,
+        phase_deadline: now,
+        hosts_path: std::path::PathBuf::from("/etc/hosts"),
+        apps_killed: Vec::new(),
+        apps_frozen: vec![],
+        paused: Some(PauseState { since: now, remaining_secs: 600, blocks_lifted: false }),
+        blockers: vec!["hosts file /etc/hosts".to_string()],
+        categories: vec![],
+    };
+
+    assert_eq!(state.remaining(), chrono::Duration::seconds(600));
+}
+
+#[test]
+fn test_extend_shorten_parsing() {
+    use clap::Parser;
+
+    let cli = Cli::try_parse_from(["flowmode", "extend", "10m"]).unwrap();
+    match cli.command {
+        CliCommand::Extend(adjust_args) => {
+            assert_eq!(adjust_args.duration, "10m");
+            assert!(!adjust_args.session);
+        }
+        _ => panic!("Expected Extend command"),
+    }
+
+    let cli = Cli::try_parse_from(["flowmode", "shorten", "--session", "15m"]).unwrap();
+    match cli.command {
+        CliCommand::Shorten(adjust_args) => {
+            assert_eq!(adjust_args.duration, "15m");
+            assert!(adjust_args.session);
+        }
+        _ => panic!("Expected Shorten command"),
+    }
+}
+
+#[test]
+fn test_strip_managed_block() {
+    let content = "127.0.0.1 localhost\n# BEGIN flowmode\n127.0.0.1 example.com\n# END flowmode\n10.0.0.1 nas.local\n";
+    assert_eq!(strip_managed_block(content), "127.0.0.1 localhost\n10.0.0.1 nas.local\n");
+
+    // Nothing to strip is a no-op
+    assert_eq!(strip_managed_block("127.0.0.1 localhost\n"), "127.0.0.1 localhost\n");
+
+    // An unterminated block is left untouched rather than guessing where it ends
+    let broken = "127.0.0.1 localhost\n# BEGIN flowmode\n127.0.0.1 example.com\n";
+    assert_eq!(strip_managed_block(broken), broken);
+}
+
+#[test]
+fn test_write_hosts_file_replaces_atomically() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let hosts_path = temp_dir.path().join("hosts");
+    std::fs::write(&hosts_path, "127.0.0.1 localhost\n").unwrap();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&hosts_path, std::fs::Permissions::from_mode(0o640)).unwrap();
+    }
+
+    write_hosts_file(&hosts_path, "127.0.0.1 localhost\n127.0.0.1 example.com\n").unwrap();
+
+    assert_eq!(std::fs::read_to_string(&hosts_path).unwrap(), "127.0.0.1 localhost\n127.0.0.1 example.com\n");
+    // Only the hosts file itself is left in the directory
+    assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&hosts_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_write_hosts_file_keeps_symlink() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let real_dir = temp_dir.path().join("real");
+    std::fs::create_dir(&real_dir).unwrap();
+    let real_path = real_dir.join("hosts.real");
+    std::fs::write(&real_path, "127.0.0.1 localhost\n").unwrap();
+    let hosts_path = temp_dir.path().join("hosts");
+    std::os::unix::fs::symlink(&real_path, &hosts_path).unwrap();
+
+    write_hosts_file(&hosts_path, "127.0.0.1 localhost\n127.0.0.1 example.com\n").unwrap();
+
+    // The link still points at the real file, which has the new content
+    assert!(std::fs::symlink_metadata(&hosts_path).unwrap().file_type().is_symlink());
+    assert_eq!(std::fs::read_link(&hosts_path).unwrap(), real_path);
+    assert_eq!(std::fs::read_to_string(&real_path).unwrap(), "127.0.0.1 localhost\n127.0.0.1 example.com\n");
+    // The temporary file was created next to the real file and is gone again
+    assert_eq!(std::fs::read_dir(&real_dir).unwrap().count(), 1);
+    assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn test_write_hosts_file_reports_missing_directory() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let hosts_path = temp_dir.path().join("missing").join("hosts");
+
+    let error = write_hosts_file(&hosts_path, "127.0.0.1 localhost\n").unwrap_err();
+    assert!(error.to_string().contains("Failed to prepare the new version of"));
+}
+
+// The hosts entries generated for `block_list` with the rest of `config`.
+fn entries_for(block_list: &[&str], config: &Config) -> Vec<(String, String)> {
+    let config = Config { block_list: Some(block_list.iter().map(|s| s.to_string()).collect()), ..config.clone() };
+    hosts_entries(&BlockPlan::from_config(&config, false))
+}
+
+#[test]
+fn test_hosts_entries_from_domains() {
+    let entry = |address: &str, host: &str| (address.to_string(), host.to_string());
+    let block_list = ["Reddit.com", "www.news.com", "0.0.0.0 legacy.com", "reddit.com"];
+    let ipv4_only = Config { redirect_addresses: Some(vec!["127.0.0.1".to_string()]), ..Config::default() };
+
+    assert_eq!(entries_for(&block_list, &ipv4_only), vec![
+        entry("127.0.0.1", "reddit.com"),
+        entry("127.0.0.1", "www.reddit.com"),
+        entry("127.0.0.1", "www.news.com"),
+        entry("0.0.0.0", "legacy.com"),
+    ]);
+
+    let config = Config { subdomains: Some(vec!["m".to_string(), "mobile".to_string()]), ..ipv4_only.clone() };
+    assert_eq!(entries_for(&block_list[..1], &config), vec![
+        entry("127.0.0.1", "reddit.com"),
+        entry("127.0.0.1", "m.reddit.com"),
+        entry("127.0.0.1", "mobile.reddit.com"),
+    ]);
+    let config = Config { subdomains: Some(vec![]), ..ipv4_only };
+    assert_eq!(entries_for(&block_list[..1], &config), vec![entry("127.0.0.1", "reddit.com")]);
+}
+
+#[test]
+fn test_hosts_entries_strip_wildcards() {
+    let entry = |address: &str, host: &str| (address.to_string(), host.to_string());
+    let ipv4_only = Config { redirect_addresses: Some(vec!["127.0.0.1".to_string()]), ..Config::default() };
+
+    // A hosts file cannot match "*.", so the domain and its subdomains are blocked instead
+    assert_eq!(entries_for(&["*.example.com", "0.0.0.0 *.legacy.com"], &ipv4_only), vec![
+        entry("127.0.0.1", "example.com"),
+        entry("127.0.0.1", "www.example.com"),
+        entry("0.0.0.0", "legacy.com"),
+    ]);
+}
+
+#[test]
+fn test_hosts_entries_cover_ipv6() {
+    let entry = |address: &str, host: &str| (address.to_string(), host.to_string());
+    let config = Config { subdomains: Some(vec![]), ..Config::default() };
+
+    assert_eq!(entries_for(&["example.com"], &config), vec![
+        entry("127.0.0.1", "example.com"),
+        entry("::1", "example.com"),
+    ]);
+    // Legacy lines keep their own address and gain the other family
+    assert_eq!(entries_for(&["0.0.0.0 legacy.com", ":: other.com"], &config), vec![
+        entry("0.0.0.0", "legacy.com"),
+        entry("::1", "legacy.com"),
+        entry("::", "other.com"),
+        entry("127.0.0.1", "other.com"),
+    ]);
+
+    let config = Config { redirect_addresses: Some(vec!["0.0.0.0".to_string(), "::".to_string(), "bogus".to_string()]), ..config };
+    assert_eq!(entries_for(&["example.com"], &config), vec![
+        entry("0.0.0.0", "example.com"),
+        entry("::", "example.com"),
+    ]);
+}
+
+#[test]
+fn test_allowlist_filter() {
+    let whitelist = vec!["GitHub.com".to_string(), "*.rust-lang.org".to_string(), "*.".to_string()];
+    let filter = DnsFilter::allowlist(&whitelist, &["www".to_string()]);
+
+    assert!(!filter.is_blocked("github.com"));
+    assert!(!filter.is_blocked("www.github.com."));
+    assert!(filter.is_blocked("api.github.com"));
+    assert!(!filter.is_blocked("rust-lang.org"));
+    assert!(!filter.is_blocked("doc.rust-lang.org"));
+    assert!(filter.is_blocked("trust-lang.org"));
+    assert!(filter.is_blocked("example.com"));
+
+    // An empty allowlist blocks everything
+    assert!(DnsFilter::allowlist(&[], &[]).is_blocked("github.com"));
+}
+
+#[test]
+fn test_blocklist_filter() {
+    let block_list = vec!["Reddit.com".to_string(), "*.youtube.com".to_string(), "127.0.0.1 ads.example.com".to_string()];
+    let filter = DnsFilter::blocklist(&block_list);
+
+    assert!(filter.is_blocked("reddit.com"));
+    assert!(filter.is_blocked("old.reddit.com."));
+    assert!(filter.is_blocked("youtube.com"));
+    assert!(filter.is_blocked("www.youtube.com"));
+    assert!(filter.is_blocked("ads.example.com"));
+    assert!(!filter.is_blocked("example.com"));
+    assert!(!filter.is_blocked("cdn.ads.example.com"));
+    assert!(!filter.is_blocked("notreddit.com"));
+}
+
+#[test]
+fn test_nftables_ruleset() {
+    let addresses: Vec<std::net::IpAddr> = vec!["93.184.216.34".parse().unwrap(), "2606:2800:220:1::1".parse().unwrap(), "198.51.100.2".parse().unwrap()];
+    let ruleset = nftables_ruleset(&addresses);
+
+    assert!(ruleset.starts_with("table inet flowmode {\n"));
+    assert!(ruleset.contains("type ipv4_addr\n        elements = { 93.184.216.34, 198.51.100.2 }"));
+    assert!(ruleset.contains("type ipv6_addr\n        elements = { 2606:2800:220:1::1 }"));
+    assert!(ruleset.contains("type filter hook output priority 0; policy accept;"));
+    assert!(ruleset.contains("ip daddr @blocked_ipv4 reject"));
+    assert!(ruleset.contains("ip6 daddr @blocked_ipv6 reject"));
+
+    // nft rejects an empty elements list, so empty sets leave it out
+    assert!(!nftables_ruleset(&[]).contains("elements"));
+}
+
+#[test]
+fn test_nftables_dry_run() {
+    fn resolve(host: &str) -> std::io::Result<Vec<std::net::IpAddr>> {
+        match host {
+            "example.com" => Ok(vec!["93.184.216.34".parse().unwrap(), "127.0.0.1".parse().unwrap()]),
+            "www.example.com" => Ok(vec!["93.184.216.34".parse().unwrap(), "2606:2800:220:1::1".parse().unwrap()]),
+            _ => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "unknown host")),
+        }
+    }
+    let config = Config { block_list: Some(vec!["example.com".to_string(), "missing.test".to_string()]), ..Config::default() };
+    let plan = BlockPlan::from_config(&config, false);
+    let mut blocker = NftablesBlocker::with_resolver(true, resolve);
+
+    assert!(blocker.verify(&plan).is_err());
+    blocker.apply(&plan).unwrap();
+    // Loopback answers (e.g. from the hosts backend) are never rejected
+    let expected: Vec<std::net::IpAddr> = vec!["93.184.216.34".parse().unwrap(), "2606:2800:220:1::1".parse().unwrap()];
+    assert_eq!(blocker.addresses(), expected.as_slice());
+    blocker.verify(&plan).unwrap();
+    assert_eq!(blocker.describe(), "nftables table inet flowmode (dry run)");
+
+    blocker.revert().unwrap();
+    assert!(blocker.addresses().is_empty());
+    assert!(blocker.verify(&plan).is_err());
+
+    let whitelist = BlockPlan::from_config(&config, true);
+    assert!(blocker.apply(&whitelist).is_err());
+
+    // The firewall resolves the domains before the hosts file redirects them
+    if cfg!(target_os = "linux") {
+        let config = Config {
+            blocking_backends: Some(vec![BlockingBackend::Hosts, BlockingBackend::Nftables]),
+            nftables: Some(NftablesConfig { dry_run: Some(true) }),
+            ..config
+        };
+        let descriptions: Vec<String> = build_blockers(&config, false).unwrap().iter().map(|blocker| blocker.describe()).collect();
+        assert_eq!(descriptions[0], "nftables table inet flowmode (dry run)");
+        assert!(descriptions[1].starts_with("hosts file"));
+    }
+}
+
+#[test]
+fn test_nftables_resolves_in_parallel_and_skips_slow_lookups() {
+    fn resolve(host: &str) -> std::io::Result<Vec<std::net::IpAddr>> {
+        if host == "slow.test" {
+            std::thread::sleep(std::time::Duration::from_secs(30));
+        } else {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+        }
+        let index: u8 = host.trim_start_matches("host").trim_end_matches(".test").parse().unwrap_or(0);
+        Ok(vec![std::net::IpAddr::from([198, 51, 100, index])])
+    }
+    let mut block_list: Vec<String> = (1..=40).map(|index| format!("host{}.test", index)).collect();
+    block_list.push("slow.test".to_string());
+    let config = Config { block_list: Some(block_list), subdomains: Some(Vec::new()), ..Config::default() };
+    let mut blocker = NftablesBlocker::with_resolver(true, resolve);
+
+    // One by one this would take 8 seconds for the fast names alone, and the
+    // slow one would hold everything up for 30
+    let started = std::time::Instant::now();
+    blocker.apply(&BlockPlan::from_config(&config, false)).unwrap();
+    assert!(started.elapsed() < std::time::Duration::from_secs(5), "took {:?}", started.elapsed());
+    assert_eq!(blocker.addresses().len(), 40);
+    assert!(!blocker.addresses().contains(&std::net::IpAddr::from([198, 51, 100, 0])));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_nftables_lookups_leave_the_runtime_running() {
+    fn resolve(_host: &str) -> std::io::Result<Vec<std::net::IpAddr>> {
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        Ok(vec![std::net::IpAddr::from([198, 51, 100, 1])])
+    }
+    static TICKS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    let config = Config { block_list: Some(vec!["example.com".to_string()]), subdomains: Some(Vec::new()), ..Config::default() };
+
+    // With a single worker, a ticking task only gets to run while the lookup
+    // is in progress if the lookup gives the worker up
+    let ticker = tokio::spawn(async {
+        loop {
+            TICKS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+    });
+    let blocking = tokio::spawn(async move {
+        let mut blocker = NftablesBlocker::with_resolver(true, resolve);
+        blocker.apply(&BlockPlan::from_config(&config, false)).unwrap();
+        TICKS.load(std::sync::atomic::Ordering::SeqCst)
+    });
+    let ticks = blocking.await.unwrap();
+    ticker.abort();
+    assert!(ticks >= 10, "ticked {} times during the lookup", ticks);
+}
+
+#[test]
+fn test_nftables_keeps_addresses_resolved_earlier() {
+    static REDIRECTED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+    // Once the hosts backend is in place every blocked domain resolves to loopback
+    fn resolve(host: &str) -> std::io::Result<Vec<std::net::IpAddr>> {
+        if REDIRECTED.load(std::sync::atomic::Ordering::SeqCst) {
+            return Ok(vec!["127.0.0.1".parse().unwrap()]);
+        }
+        match host {
+            "example.com" => Ok(vec!["93.184.216.34".parse().unwrap()]),
+            _ => Ok(vec!["198.51.100.2".parse().unwrap()]),
+        }
+    }
+    let config = Config { block_list: Some(vec!["example.com".to_string()]), subdomains: Some(Vec::new()), ..Config::default() };
+    let base = BlockPlan::from_config(&config, false);
+    let mut blocker = NftablesBlocker::with_resolver(true, resolve);
+    blocker.apply(&base).unwrap();
+    REDIRECTED.store(true, std::sync::atomic::Ordering::SeqCst);
+
+    // A schedule adds a domain: the known one keeps its address
+    let mut plan = base.clone();
+    plan.add_domains(["news.test".to_string()]);
+    blocker.update(&plan).unwrap();
+    let expected: Vec<std::net::IpAddr> = vec!["93.184.216.34".parse().unwrap()];
+    assert_eq!(blocker.addresses(), expected.as_slice());
+
+    // Putting the table back after tampering does not lose it either
+    blocker.apply(&plan).unwrap();
+    assert_eq!(blocker.addresses(), expected.as_slice());
+}
+
+#[test]
+fn test_check_resolution_reports_reachable_domains() {
+    fn resolve(host: &str) -> std::io::Result<Vec<std::net::IpAddr>> {
+        let addresses: &[&str] = match host {
+            "blocked.com" | "www.blocked.com" => &["127.0.0.1", "::1"],
+            "leaky.com" => &["93.184.216.34", "127.0.0.1"],
+            "www.leaky.com" => &["0.0.0.0"],
+            "sinkhole.com" => &["10.0.0.1"],
+            _ => return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "unknown host")),
+        };
+        Ok(addresses.iter().map(|address| address.parse().unwrap()).collect())
+    }
+    let block_list = ["blocked.com", "leaky.com", "gone.com", "10.0.0.1 sinkhole.com"].iter().map(|s| s.to_string()).collect();
+    let plan = BlockPlan::from_config(&Config { block_list: Some(block_list), ..Config::default() }, false);
+
+    // Redirect addresses, the address of a hosts-style entry and names that no longer resolve all count as blocked
+    assert_eq!(check_resolution(&plan, resolve), vec![UnblockedHost {
+        host: "leaky.com".to_string(),
+        addresses: vec!["93.184.216.34".parse().unwrap()],
+    }]);
+}
+
+// Records every command and fails the ones for programs listed in `missing`.
+struct RecordingRunner {
+    missing: Vec<&'static str>,
+    calls: std::cell::RefCell<Vec<String>>,
+}
+
+impl CommandRunner for RecordingRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        self.calls.borrow_mut().push(format!("{} {}", program, args.join(" ")));
+        if self.missing.contains(&program) {
+            Err("not installed".into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn test_flush_dns_caches() {
+    let runner = RecordingRunner { missing: vec!["nscd"], calls: Default::default() };
+    let commands = vec!["resolvectl flush-caches".to_string(), "  nscd   --invalidate=hosts ".to_string(), "   ".to_string()];
+
+    let results = flush_dns_caches(&commands, &runner);
+    // Every command is tried, even after one succeeded; blank ones are skipped
+    assert_eq!(*runner.calls.borrow(), vec!["resolvectl flush-caches", "nscd --invalidate=hosts"]);
+    assert_eq!(results, vec![
+        FlushResult { command: "resolvectl flush-caches".to_string(), error: None },
+        FlushResult { command: "nscd   --invalidate=hosts".to_string(), error: Some("not installed".to_string()) },
+    ]);
+
+    assert!(!default_flush_commands().is_empty());
+}
+
+#[test]
+fn test_parse_block_list() {
+    let content = "\
+# Title: Example hosts list
+[Adblock Plus 2.0]
+! Expires: 1 day
+127.0.0.1 localhost
+::1 ip6-localhost ip6-loopback
+0.0.0.0 0.0.0.0
+0.0.0.0 Ads.Example.com tracker.example.net # inline comment
+0.0.0.0 ads.example.com
+social.example.org.
+not a domain line
+-bad.example.com
+";
+    assert_eq!(parse_block_list(content), vec!["ads.example.com", "tracker.example.net", "social.example.org"]);
+
+    // Imported domains already named in block_list are not added twice
+    let mut plan = BlockPlan::from_config(&Config { block_list: Some(vec!["Social.example.org".to_string()]), ..Config::default() }, false);
+    assert_eq!(plan.add_domains(parse_block_list(content)), 2);
+    assert_eq!(plan.block_list, vec!["Social.example.org", "ads.example.com", "tracker.example.net"]);
+}
+
+#[test]
+fn test_select_categories() {
+    let mut categories = std::collections::BTreeMap::new();
+    categories.insert("social".to_string(), vec!["facebook.com".to_string(), "0.0.0.0 instagram.com".to_string()]);
+    categories.insert("news".to_string(), vec!["news.example.com".to_string()]);
+    let config = Config { block_list: Some(vec!["reddit.com".to_string()]), categories: Some(categories), ..Config::default() };
+    let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+
+    let selection = select_categories(&config, &names(&["Social", "extra.com", "social"]), &names(&["news", "Reddit.com."])).unwrap();
+    assert_eq!(selection.categories, vec!["social"]);
+    assert_eq!(selection.block, vec!["facebook.com", "0.0.0.0 instagram.com", "extra.com"]);
+    assert_eq!(selection.allow, vec!["news.example.com", "reddit.com"]);
+
+    let error = select_categories(&config, &names(&["socail"]), &[]).unwrap_err();
+    assert_eq!(error.to_string(), "Unknown category 'socail'. Available categories: news, social");
+
+    // --allow takes entries out whatever form they are written in
+    let mut plan = BlockPlan::from_config(&config, false);
+    plan.add_domains(selection.block);
+    assert_eq!(plan.remove_domains(&names(&["instagram.com", "reddit.com"])), 2);
+    assert_eq!(plan.block_list, vec!["facebook.com", "extra.com"]);
+}
+
+#[test]
+fn test_browser_policies() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let firefox_dir = temp_dir.path().join("firefox");
+    let chromium_dir = temp_dir.path().join("chromium").join("managed");
+    std::fs::create_dir_all(&firefox_dir).unwrap();
+    let admin_policies = r#"{"policies": {"DisableTelemetry": true}}"#;
+    std::fs::write(firefox_dir.join("policies.json"), admin_policies).unwrap();
+    let read = |path: std::path::PathBuf| serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(path).unwrap()).unwrap();
+
+    let config = Config {
+        block_list: Some(vec!["example.com".to_string(), "0.0.0.0 *.tracker.test".to_string()]),
+        whitelist: Some(vec!["github.com".to_string(), "*.rust-lang.org".to_string()]),
+        ..Config::default()
+    };
+    let plan = BlockPlan::from_config(&config, false);
+    let mut blocker = BrowserPolicyBlocker::new(Some(firefox_dir.clone()), vec![chromium_dir.clone()]);
+    assert!(blocker.verify(&plan).is_err());
+    blocker.apply(&plan).unwrap();
+    blocker.verify(&plan).unwrap();
+
+    // The admin's own policies are kept alongside ours
+    let firefox = read(firefox_dir.join("policies.json"));
+    assert_eq!(firefox["policies"]["DisableTelemetry"], true);
+    assert_eq!(firefox["policies"]["DNSOverHTTPS"]["Enabled"], false);
+    assert_eq!(
+        firefox["policies"]["WebsiteFilter"]["Block"],
+        serde_json::json!(["*://example.com/*", "*://*.example.com/*", "*://tracker.test/*", "*://*.tracker.test/*"])
+    );
+    let chromium = read(chromium_dir.join("flowmode.json"));
+    assert_eq!(chromium["URLBlocklist"], serde_json::json!(["example.com", "tracker.test"]));
+    assert_eq!(chromium["DnsOverHttpsMode"], "off");
+    assert_eq!(blocker.watched_paths(), vec![firefox_dir.join("policies.json"), chromium_dir.join("flowmode.json")]);
+
+    // Applying again, as after a pause, still restores the original file
+    blocker.apply(&plan).unwrap();
+    std::fs::write(chromium_dir.join("flowmode.json"), "{}").unwrap();
+    assert!(blocker.verify(&plan).is_err());
+    blocker.revert().unwrap();
+    assert_eq!(std::fs::read_to_string(firefox_dir.join("policies.json")).unwrap(), admin_policies);
+    assert_eq!(std::fs::read_dir(&firefox_dir).unwrap().count(), 1);
+    assert!(!chromium_dir.join("flowmode.json").exists());
+
+    // Whitelist mode blocks everything except the allowed sites
+    let whitelist = BlockPlan::from_config(&config, true);
+    std::fs::remove_file(firefox_dir.join("policies.json")).unwrap();
+    blocker.apply(&whitelist).unwrap();
+    let firefox = read(firefox_dir.join("policies.json"));
+    assert_eq!(firefox["policies"]["WebsiteFilter"]["Block"], serde_json::json!(["<all_urls>"]));
+    assert_eq!(
+        firefox["policies"]["WebsiteFilter"]["Exceptions"],
+        serde_json::json!(["*://github.com/*", "*://www.github.com/*", "*://rust-lang.org/*", "*://*.rust-lang.org/*"])
+    );
+    let chromium = read(chromium_dir.join("flowmode.json"));
+    assert_eq!(chromium["URLBlocklist"], serde_json::json!(["*"]));
+    assert_eq!(chromium["URLAllowlist"], serde_json::json!([".github.com", ".www.github.com", "rust-lang.org"]));
+    blocker.revert().unwrap();
+    assert_eq!(std::fs::read_dir(&firefox_dir).unwrap().count(), 0);
+}
+
+#[test]
+fn test_render_block_page() {
+    let now = chrono::Local::now();
+    let mut state = SessionState {
+        pid: 1,
+        task: "Write <docs> & tests".to_string(),
+        started_at: now,
+        phase: Phase::ShortBreak,
+        cycle: 1,
+        total_cycles: 4,
+        phase_deadline: now + chrono::Duration::seconds(125),
+        hosts_path: std::path::PathBuf::from("/etc/hosts"),
+        apps_killed: vec![],
+        apps_frozen: vec![],
+        paused: None,
+        blockers: vec![],
+        categories: vec![],
+    };
+
+    let page = render_block_page(DEFAULT_BLOCK_PAGE, Some(&state), "news.example.com");
+    assert!(page.contains("<title>news.example.com is blocked</title>"));
+    assert!(page.contains("Write &lt;docs&gt; &amp; tests"));
+    assert!(page.contains("left in this short break phase"));
+
+    state.paused = Some(PauseState { since: now, remaining_secs: 3725, blocks_lifted: false });
+    assert_eq!(render_block_page("{{remaining}} {{domain}}", Some(&state), ""), "01:02:05 (paused) This site");
+    assert_eq!(render_block_page("{{task}}: {{remaining}}", None, "a.com"), "your focus session: --:--");
+}
+
+#[test]
+fn test_schedules() {
+    let window = |days: &[&str], hours: Option<&str>| ScheduleWindow {
+        days: (!days.is_empty()).then(|| days.iter().map(|day| day.to_string()).collect()),
+        hours: hours.map(str::to_string),
+    };
+    let mut categories = std::collections::BTreeMap::new();
+    categories.insert("news".to_string(), vec!["news.example.com".to_string(), "0.0.0.0 paper.example".to_string()]);
+    categories.insert("video".to_string(), vec!["video.example".to_string()]);
+    let mut schedules = std::collections::BTreeMap::new();
+    schedules.insert("news".to_string(), vec![window(&["mon-fri"], Some("09:00-17:00"))]);
+    schedules.insert("reddit.com".to_string(), vec![window(&["Saturday", "sun"], None), window(&[], Some("22:00-01:30"))]);
+    schedules.insert("video".to_string(), vec![window(&["weekdays"], None)]);
+    let config = Config {
+        block_list: Some(vec!["reddit.com".to_string(), "example.com".to_string()]),
+        categories: Some(categories),
+        schedules: Some(schedules),
+        ..Config::default()
+    };
+    let at = |text: &str| chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap();
+
+    // video was chosen with --block, so it is blocked all session; paper.example is allowed
+    let selection = Selection {
+        categories: vec!["video".to_string()],
+        block: vec!["video.example".to_string()],
+        allow: vec!["paper.example".to_string()],
+    };
+    let schedules = Schedules::from_config(&config, &selection).unwrap();
+    assert_eq!(schedules.scheduled_domains(), vec!["reddit.com"]);
+    // 2024-01-05 is a Friday
+    assert_eq!(schedules.active(at("2024-01-05 08:59")), Vec::<String>::new());
+    assert_eq!(schedules.active(at("2024-01-05 09:00")), vec!["news"]);
+    assert_eq!(schedules.active(at("2024-01-05 23:00")), vec!["reddit.com"]);
+    assert_eq!(schedules.active(at("2024-01-06 01:00")), vec!["reddit.com"]);
+    assert_eq!(schedules.active(at("2024-01-08 01:00")), vec!["reddit.com"]);
+    assert_eq!(schedules.active(at("2024-01-08 01:30")), Vec::<String>::new());
+    assert_eq!(schedules.next_change(at("2024-01-05 12:00")), Some(at("2024-01-05 17:00")));
+    assert_eq!(schedules.next_change(at("2024-01-05 17:00")), Some(at("2024-01-05 22:00")));
+
+    let mut base = BlockPlan::from_config(&config, false);
+    base.remove_domains(&schedules.scheduled_domains());
+    assert_eq!(base.block_list, vec!["example.com"]);
+    let plan = schedules.plan(&base, &["news".to_string(), "reddit.com".to_string()]);
+    assert_eq!(plan.block_list, vec!["example.com", "news.example.com", "reddit.com"]);
+
+    // A scheduled domain named with --block is blocked all session too
+    let selection = Selection { block: vec!["Reddit.com".to_string()], ..Selection::default() };
+    let schedules = Schedules::from_config(&config, &selection).unwrap();
+    assert!(schedules.scheduled_domains().is_empty());
+    assert_eq!(schedules.active(at("2024-01-06 12:00")), Vec::<String>::new());
+
+    let invalid = |windows: Vec<ScheduleWindow>| {
+        let mut schedules = std::collections::BTreeMap::new();
+        schedules.insert("news".to_string(), windows);
+        let config = Config { schedules: Some(schedules), ..config.clone() };
+        Schedules::from_config(&config, &Selection::default()).unwrap_err().to_string()
+    };
+    assert_eq!(invalid(vec![window(&["mnday"], None)]), "Invalid schedule for 'news': unknown day 'mnday', use one of mon, tue, wed, thu, fri, sat, sun");
+    assert_eq!(invalid(vec![window(&[], Some("9-17"))]), "Invalid schedule for 'news': '9' is not a time like 09:00");
+}